@@ -0,0 +1,141 @@
+use crate::ray::RayR3;
+use crate::vec3::{Vec3, VecR3};
+use std::f64;
+
+/// An axis-aligned bounding box
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: VecR3,
+    pub max: VecR3,
+}
+
+impl Aabb {
+    /// Create a bounding box from its minimum and maximum corners.
+    pub fn new(min: VecR3, max: VecR3) -> Self {
+        Self { min, max }
+    }
+
+    /// An empty bounding box; the identity for `union`.
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    /// The smallest bounding box containing two points.
+    pub fn from_points(a: VecR3, b: VecR3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    /// The smallest bounding box containing both boxes.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// The largest bounding box contained in both boxes.
+    ///
+    /// The result is empty if the boxes do not overlap.
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        }
+    }
+
+    /// The smallest bounding box containing this box and a point.
+    pub fn grow(&self, point: VecR3) -> Self {
+        Self {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    /// Whether the box contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// The center point of the box.
+    pub fn centroid(&self) -> VecR3 {
+        (self.min + self.max) / 2.0
+    }
+
+    /// The vector from the minimum corner to the maximum corner.
+    pub fn diagonal(&self) -> VecR3 {
+        self.max - self.min
+    }
+
+    /// Total area of the box faces.
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.diagonal();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Index of the axis along which the box is longest.
+    pub fn largest_axis(&self) -> usize {
+        let d = self.diagonal();
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Check whether a ray passes through the box between t_min and t_max.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to test.
+    /// * `inv_direction` - Elementwise reciprocal of `ray.direction`.
+    /// * `t_min` - Minimum ray position.
+    /// * `t_max` - Maximum ray position.
+    #[inline]
+    pub fn hit(&self, ray: &RayR3, inv_direction: &VecR3, t_min: f64, t_max: f64) -> bool {
+        self.clip(ray, inv_direction, t_min, t_max).is_some()
+    }
+
+    /// The part of the range of ray positions from t_min to t_max that is inside the box.
+    ///
+    /// Arguments are the same as for `hit`.
+    ///
+    /// # Returns
+    /// The first and last ray positions inside the box, or None if the ray misses the box.
+    #[inline]
+    pub fn clip(
+        &self,
+        ray: &RayR3,
+        inv_direction: &VecR3,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(f64, f64)> {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - ray.origin[axis]) * inv_direction[axis];
+            let t1 = (self.max[axis] - ray.origin[axis]) * inv_direction[axis];
+            let (t0, t1) = if inv_direction[axis] < 0.0 {
+                (t1, t0)
+            } else {
+                (t0, t1)
+            };
+            // f64::max / f64::min ignore NaN, which occurs for rays parallel to a slab face.
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
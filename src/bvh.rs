@@ -0,0 +1,416 @@
+use crate::aabb::Aabb;
+use crate::ray::RayR3;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::{Vec3, VecR3};
+use std::sync::Arc;
+
+/// Number of centroid bins used when evaluating the surface area heuristic.
+const NUM_BINS: usize = 12;
+/// Leaves may be larger than this only if their primitives cannot be separated.
+const MAX_LEAF_SIZE: usize = 4;
+/// Cost of traversing an interior node relative to intersecting one primitive.
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// A node of a flattened bounding volume hierarchy.
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: Aabb,
+    /// For a leaf, the position of its first primitive in `BvhTree::indices`.
+    /// For an interior node, the index of the second child;
+    /// the first child is stored immediately after its parent.
+    offset: usize,
+    /// Number of primitives in a leaf; 0 for interior nodes.
+    count: usize,
+    /// Axis along which the children of an interior node were split.
+    axis: usize,
+}
+
+/// A bounding volume hierarchy over primitives identified by index.
+///
+/// Stores only the tree structure;
+/// the primitives themselves are owned and intersected by the caller.
+#[derive(Debug, Clone)]
+pub struct BvhTree {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl BvhTree {
+    /// Build a hierarchy using the surface area heuristic.
+    ///
+    /// # Arguments
+    /// * `bounds` - The bounding box of each primitive. Primitive `i` has bounds `bounds[i]`.
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut indices: Vec<usize> = (0..bounds.len()).collect();
+        let centroids: Vec<VecR3> = bounds.iter().map(Aabb::centroid).collect();
+        let mut nodes = Vec::with_capacity(2 * bounds.len());
+        if !bounds.is_empty() {
+            build(&mut nodes, &mut indices, 0, bounds, &centroids);
+        }
+        Self { nodes, indices }
+    }
+
+    /// Bounding box of all primitives in the tree; None if the tree is empty.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| node.bounds)
+    }
+
+    /// Find the nearest primitive intersection along a ray.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray to intersect.
+    /// * `t_min` - Minimum ray position of an intersection.
+    /// * `t_max` - Maximum ray position of an intersection.
+    /// * `intersect_primitive` - Intersects the ray with a primitive given its index
+    ///   and the current maximum ray position.
+    pub fn intersect<'a, F>(
+        &self,
+        ray: &RayR3,
+        t_min: f64,
+        t_max: f64,
+        mut intersect_primitive: F,
+    ) -> Option<Intersection<'a>>
+    where
+        F: FnMut(usize, f64) -> Option<Intersection<'a>>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_direction = Vec3::new(
+            ray.direction.x.recip(),
+            ray.direction.y.recip(),
+            ray.direction.z.recip(),
+        );
+        let direction_is_negative = [
+            inv_direction.x < 0.0,
+            inv_direction.y < 0.0,
+            inv_direction.z < 0.0,
+        ];
+
+        let mut result = None;
+        let mut closest_so_far = t_max;
+        let mut to_visit = Vec::with_capacity(64);
+        let mut node_index = 0;
+        loop {
+            let node = &self.nodes[node_index];
+            if node.bounds.hit(ray, &inv_direction, t_min, closest_so_far) {
+                if node.count > 0 {
+                    for &i in &self.indices[node.offset..node.offset + node.count] {
+                        if let Some(intersection) = intersect_primitive(i, closest_so_far) {
+                            closest_so_far = intersection.t;
+                            result = Some(intersection);
+                        }
+                    }
+                } else {
+                    // Visit the child nearer to the ray origin first
+                    if direction_is_negative[node.axis] {
+                        to_visit.push(node_index + 1);
+                        node_index = node.offset;
+                    } else {
+                        to_visit.push(node.offset);
+                        node_index += 1;
+                    }
+                    continue;
+                }
+            }
+            match to_visit.pop() {
+                Some(i) => node_index = i,
+                None => break,
+            }
+        }
+        result
+    }
+}
+
+/// Recursively build the subtree containing `indices`.
+///
+/// `offset` is the position of `indices` within the full index list.
+/// Returns the index of the created node.
+fn build(
+    nodes: &mut Vec<Node>,
+    indices: &mut [usize],
+    offset: usize,
+    bounds: &[Aabb],
+    centroids: &[VecR3],
+) -> usize {
+    let node_index = nodes.len();
+    let node_bounds = indices
+        .iter()
+        .fold(Aabb::empty(), |acc, &i| acc.union(&bounds[i]));
+    nodes.push(Node {
+        bounds: node_bounds,
+        offset,
+        count: indices.len(),
+        axis: 0,
+    });
+    if indices.len() == 1 {
+        return node_index;
+    }
+
+    let centroid_bounds = indices
+        .iter()
+        .fold(Aabb::empty(), |acc, &i| acc.grow(centroids[i]));
+    let axis = centroid_bounds.largest_axis();
+    let axis_min = centroid_bounds.min[axis];
+    let axis_extent = centroid_bounds.max[axis] - axis_min;
+    if axis_extent <= 0.0 {
+        // All centroids coincide; no split can separate the primitives.
+        return node_index;
+    }
+
+    let bin_of = |i: usize| -> usize {
+        let b = ((centroids[i][axis] - axis_min) / axis_extent * NUM_BINS as f64) as usize;
+        b.min(NUM_BINS - 1)
+    };
+
+    let mut bin_counts = [0usize; NUM_BINS];
+    let mut bin_bounds = [Aabb::empty(); NUM_BINS];
+    for &i in indices.iter() {
+        let b = bin_of(i);
+        bin_counts[b] += 1;
+        bin_bounds[b] = bin_bounds[b].union(&bounds[i]);
+    }
+
+    // Sweep from the right to accumulate the area and count of each right partition
+    let mut right_area = [0.0; NUM_BINS];
+    let mut right_count = [0usize; NUM_BINS];
+    let mut acc_bounds = Aabb::empty();
+    let mut acc_count = 0;
+    for b in (1..NUM_BINS).rev() {
+        acc_bounds = acc_bounds.union(&bin_bounds[b]);
+        acc_count += bin_counts[b];
+        right_area[b] = acc_bounds.surface_area();
+        right_count[b] = acc_count;
+    }
+
+    // Sweep from the left to find the split with lowest cost.
+    // Split `s` places bins 0..=s on the left.
+    let mut best_split = 0;
+    let mut best_cost = f64::INFINITY;
+    let mut acc_bounds = Aabb::empty();
+    let mut acc_count = 0;
+    for s in 0..NUM_BINS - 1 {
+        acc_bounds = acc_bounds.union(&bin_bounds[s]);
+        acc_count += bin_counts[s];
+        if acc_count == 0 || right_count[s + 1] == 0 {
+            continue;
+        }
+        let cost = acc_bounds.surface_area() * acc_count as f64
+            + right_area[s + 1] * right_count[s + 1] as f64;
+        if cost < best_cost {
+            best_cost = cost;
+            best_split = s;
+        }
+    }
+
+    let node_area = node_bounds.surface_area();
+    let split_cost = if node_area > 0.0 {
+        TRAVERSAL_COST + best_cost / node_area
+    } else {
+        TRAVERSAL_COST
+    };
+    if indices.len() <= MAX_LEAF_SIZE && split_cost >= indices.len() as f64 {
+        return node_index;
+    }
+
+    // Partition the indices in-place so that the left side comes first
+    let mut mid = 0;
+    for j in 0..indices.len() {
+        if bin_of(indices[j]) <= best_split {
+            indices.swap(j, mid);
+            mid += 1;
+        }
+    }
+    if mid == 0 || mid == indices.len() {
+        // Binning failed to separate the centroids; split at the median instead.
+        mid = indices.len() / 2;
+        indices.select_nth_unstable_by(mid, |&a, &b| {
+            centroids[a][axis]
+                .partial_cmp(&centroids[b][axis])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    }
+
+    let (left, right) = indices.split_at_mut(mid);
+    build(nodes, left, offset, bounds, centroids);
+    let second_child = build(nodes, right, offset + mid, bounds, centroids);
+    let node = &mut nodes[node_index];
+    node.offset = second_child;
+    node.count = 0;
+    node.axis = axis;
+    node_index
+}
+
+/// A bounding volume hierarchy over a collection of surfaces
+///
+/// A drop-in replacement for a list of surfaces that avoids testing every ray against
+/// every surface. Surfaces without a bounding box are tested individually.
+pub struct Bvh {
+    surfaces: Vec<Arc<dyn Surface>>,
+    unbounded: Vec<Arc<dyn Surface>>,
+    tree: BvhTree,
+}
+
+impl Bvh {
+    /// Build a hierarchy over a list of surfaces using the surface area heuristic.
+    ///
+    /// The surfaces are shared so that those that are lights can be sampled individually.
+    pub fn new(surfaces: Vec<Arc<dyn Surface>>) -> Self {
+        let mut bounded = Vec::with_capacity(surfaces.len());
+        let mut bounds = Vec::with_capacity(surfaces.len());
+        let mut unbounded = Vec::new();
+        for surface in surfaces {
+            match surface.bounding_box() {
+                Some(aabb) => {
+                    bounds.push(aabb);
+                    bounded.push(surface);
+                }
+                None => unbounded.push(surface),
+            }
+        }
+        Self {
+            tree: BvhTree::new(&bounds),
+            surfaces: bounded,
+            unbounded,
+        }
+    }
+}
+
+impl Surface for Bvh {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let result = self.tree.intersect(ray, t_min, t_max, |i, t_max| {
+            self.surfaces[i].intersect(ray, t_min, t_max)
+        });
+        let mut closest_so_far = result.as_ref().map_or(t_max, |intersection| intersection.t);
+        let mut closest = result;
+        for surface in &self.unbounded {
+            if let Some(intersection) = surface.intersect(ray, t_min, closest_so_far) {
+                closest_so_far = intersection.t;
+                closest = Some(intersection);
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        Some(self.tree.bounds().unwrap_or_else(Aabb::empty))
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Surface>> {
+        let mut lights = Vec::new();
+        for surface in self.surfaces.iter().chain(&self.unbounded) {
+            if surface.is_light() {
+                lights.push(Arc::clone(surface));
+            } else {
+                lights.extend(surface.lights());
+            }
+        }
+        lights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::materials::{Lambertian, Material};
+    use crate::objects::{Plane, Sphere};
+    use crate::sampling::{uniform_sphere, SampleRng};
+    use crate::surface::intersect_surfaces;
+    use rand::{Rng, SeedableRng};
+
+    /// Identifies the object that was hit by the address of its material
+    fn hit_object(intersection: &Intersection<'_>) -> usize {
+        intersection.material as *const dyn Material as *const () as usize
+    }
+
+    fn sphere(center: VecR3, radius: f64) -> Arc<dyn Surface> {
+        Arc::new(Sphere::new(
+            center,
+            radius,
+            Lambertian::new(Colour::new(0.5, 0.5, 0.5)),
+        ))
+    }
+
+    /// Check that the hierarchy finds the same nearest hits as a linear search.
+    fn assert_matches_linear(surfaces: Vec<Arc<dyn Surface>>, rng: &mut SampleRng) {
+        let bvh = Bvh::new(surfaces.clone());
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = Vec3::new(
+                rng.gen_range(-12.0..12.0),
+                rng.gen_range(-12.0..12.0),
+                rng.gen_range(-12.0..12.0),
+            );
+            let ray = RayR3::new(origin, uniform_sphere((rng.gen(), rng.gen())));
+            let t_max = if rng.gen_bool(0.2) {
+                rng.gen_range(0.0..10.0)
+            } else {
+                f64::INFINITY
+            };
+            let expected = intersect_surfaces(surfaces.iter(), &ray, 0.001, t_max);
+            let actual = bvh.intersect(&ray, 0.001, t_max);
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(expected), Some(actual)) => {
+                    assert_eq!(actual.t, expected.t);
+                    assert_eq!(hit_object(&actual), hit_object(&expected));
+                    hits += 1;
+                }
+                (expected, actual) => panic!(
+                    "linear search hit: {}, hierarchy hit: {}",
+                    expected.is_some(),
+                    actual.is_some()
+                ),
+            }
+        }
+        if !surfaces.is_empty() {
+            assert!(hits > 0);
+        }
+    }
+
+    #[test]
+    fn matches_linear_search() {
+        let mut rng = SampleRng::seed_from_u64(1);
+        let random_spheres = (0..200)
+            .map(|_| {
+                let center = Vec3::new(
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                );
+                sphere(center, rng.gen_range(0.1..1.5))
+            })
+            .collect::<Vec<_>>();
+        assert_matches_linear(random_spheres.clone(), &mut rng);
+
+        assert_matches_linear(Vec::new(), &mut rng);
+        assert_matches_linear(vec![sphere(Vec3::new(1.0, 2.0, 3.0), 4.0)], &mut rng);
+
+        // Concentric spheres all have the same centroid
+        let concentric = (1..=20)
+            .map(|i| sphere(Vec3::new(0.5, 0.0, -0.5), i as f64 * 0.5))
+            .collect();
+        assert_matches_linear(concentric, &mut rng);
+
+        // Unbounded surfaces are tested alongside the tree
+        let mut with_plane = random_spheres;
+        with_plane.push(Arc::new(Plane::new(
+            Vec3::new(0.0, -2.0, 0.0),
+            Vec3::new(0.3, 1.0, 0.0),
+            Lambertian::new(Colour::new(0.5, 0.5, 0.5)),
+        )));
+        assert_matches_linear(with_plane, &mut rng);
+    }
+
+    #[test]
+    fn empty_hierarchy_has_empty_bounds() {
+        let bvh = Bvh::new(Vec::new());
+        assert!(bvh.bounding_box().unwrap().is_empty());
+        let ray = RayR3::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(bvh.intersect(&ray, 0.001, f64::INFINITY).is_none());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::ray::RayR3;
+use crate::samplers::Sampler;
+use crate::sampling::uniform_disk;
 use crate::vec3::VecR3;
-use rand;
 
 pub struct Camera {
     origin: VecR3,
@@ -62,8 +63,9 @@ impl Camera {
     ///
     /// s in [0, 1] measures from the left to right side of the viewport.
     /// t in [0, 1] measures from the bottom to top of the viewport.
-    pub fn get_ray(&self, s: f64, t: f64) -> RayR3 {
-        let (dx, dy) = random_uniform_circle(self.lens_radius);
+    pub fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> RayR3 {
+        let (dx, dy) = uniform_disk(sampler.get_2d());
+        let (dx, dy) = (dx * self.lens_radius, dy * self.lens_radius);
         let offset = self.u * dx + self.v * dy;
 
         let origin = self.origin + offset;
@@ -71,14 +73,3 @@ impl Camera {
         RayR3 { origin, direction }
     }
 }
-
-/// Sample points from the uniform random distribution on a circle.
-fn random_uniform_circle(radius: f64) -> (f64, f64) {
-    loop {
-        let x = rand::random::<f64>() * 2.0 - 1.0;
-        let y = rand::random::<f64>() * 2.0 - 1.0;
-        if x * x + y * y < 1.0 {
-            return (x * radius, y * radius);
-        }
-    }
-}
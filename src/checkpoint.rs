@@ -0,0 +1,322 @@
+use crate::colour::Colour;
+use crate::film::FilmPixel;
+use crate::render::PixelStats;
+use anyhow::{anyhow, Context};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Identifies checkpoint files and the version of their format
+const MAGIC: &[u8; 8] = b"RTCKPT02";
+
+/// Size in bytes of the header following the magic
+const HEADER_SIZE: u64 = 3 * 4 + 3 * 8;
+
+/// Size in bytes of the film pixel and statistics of each pixel
+const PIXEL_SIZE: u64 = 4 * 8 + 4 + 4 + 2 * 8;
+
+/// The saved state of a progressive render
+///
+/// The samplers derive their random numbers from the seed, pixel and sample index,
+/// so the seed and the number of passes completed by each tile are enough to continue
+/// sampling exactly where the render stopped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub width: u32,
+    pub height: u32,
+    pub seed: u64,
+    /// `RenderSettings::fingerprint` of the settings being rendered
+    pub settings_hash: u64,
+    /// Identifies the scene being rendered
+    pub scene_hash: u64,
+    /// Number of passes completed by each tile, in the order the tiles are rendered
+    pub tile_passes: Vec<u32>,
+    /// Row-major film pixels starting from the top left
+    pub film: Vec<FilmPixel>,
+    /// Row-major sample statistics of each pixel
+    pub stats: Vec<PixelStats>,
+}
+
+impl Checkpoint {
+    /// Save the checkpoint.
+    ///
+    /// The file is written next to `path` and then renamed
+    /// so that an interrupted save does not destroy an existing checkpoint.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        self.write(&partial)
+            .and_then(|_| Ok(fs::rename(&partial, path)?))
+            .with_context(|| format!("failed to save checkpoint '{}'", path.display()))
+    }
+
+    fn write<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        let num_tiles = self.tile_passes.len() as u32;
+        for v in [self.width, self.height, num_tiles].iter() {
+            file.write_all(&v.to_le_bytes())?;
+        }
+        for v in [self.seed, self.settings_hash, self.scene_hash].iter() {
+            file.write_all(&v.to_le_bytes())?;
+        }
+        for v in &self.tile_passes {
+            file.write_all(&v.to_le_bytes())?;
+        }
+        for pixel in &self.film {
+            for v in [pixel.sum.x, pixel.sum.y, pixel.sum.z, pixel.weight].iter() {
+                file.write_all(&v.to_le_bytes())?;
+            }
+            file.write_all(&pixel.samples.to_le_bytes())?;
+        }
+        for stats in &self.stats {
+            file.write_all(&stats.count.to_le_bytes())?;
+            file.write_all(&stats.mean.to_le_bytes())?;
+            file.write_all(&stats.m2.to_le_bytes())?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Load a checkpoint saved by `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        Self::read(path).with_context(|| format!("failed to load checkpoint '{}'", path.display()))
+    }
+
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(anyhow!("not a checkpoint file"));
+        }
+        let width = read_u32(&mut file)?;
+        let height = read_u32(&mut file)?;
+        let num_tiles = read_u32(&mut file)?;
+        let seed = read_u64(&mut file)?;
+        let settings_hash = read_u64(&mut file)?;
+        let scene_hash = read_u64(&mut file)?;
+        // Check the dimensions against the file size before allocating anything for them
+        let num_pixels = width as u64 * height as u64;
+        let expected_size = PIXEL_SIZE
+            .checked_mul(num_pixels)
+            .map(|size| size + MAGIC.len() as u64 + HEADER_SIZE + 4 * num_tiles as u64);
+        if expected_size != Some(file_size) {
+            return Err(anyhow!(
+                "file is {} bytes, which does not fit a {} x {} image with {} tiles",
+                file_size,
+                width,
+                height,
+                num_tiles
+            ));
+        }
+        let tile_passes = (0..num_tiles)
+            .map(|_| read_u32(&mut file))
+            .collect::<Result<_, _>>()?;
+        let film = (0..num_pixels)
+            .map(|_| {
+                Ok(FilmPixel {
+                    sum: Colour::new(
+                        read_f64(&mut file)?,
+                        read_f64(&mut file)?,
+                        read_f64(&mut file)?,
+                    ),
+                    weight: read_f64(&mut file)?,
+                    samples: read_u32(&mut file)?,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let stats = (0..num_pixels)
+            .map(|_| {
+                Ok(PixelStats {
+                    count: read_u32(&mut file)?,
+                    mean: read_f64(&mut file)?,
+                    m2: read_f64(&mut file)?,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            width,
+            height,
+            seed,
+            settings_hash,
+            scene_hash,
+            tile_passes,
+            film,
+            stats,
+        })
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> anyhow::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> anyhow::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> anyhow::Result<f64> {
+    Ok(f64::from_bits(read_u64(reader)?))
+}
+
+/// A 64-bit FNV-1a hash, which unlike the standard library hasher is stable between releases
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::environments::Black;
+    use crate::render::{RenderSettings, Renderer};
+    use crate::surface::Surface;
+    use crate::vec3::Vec3;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("raytracer-{}-{}", std::process::id(), name))
+    }
+
+    fn checkpoint() -> Checkpoint {
+        let values = [0.1, -0.0, 1e-310, f64::MAX, f64::INFINITY, 3.0];
+        Checkpoint {
+            width: 3,
+            height: 2,
+            seed: u64::MAX,
+            settings_hash: 0x0123_4567_89ab_cdef,
+            scene_hash: 42,
+            tile_passes: vec![3, 0, 7],
+            film: (0..6)
+                .map(|i| FilmPixel {
+                    sum: Colour::new(values[i], values[(i + 1) % 6], values[(i + 2) % 6]),
+                    weight: -values[(i + 3) % 6],
+                    samples: i as u32 * 1000,
+                })
+                .collect(),
+            stats: (0..6)
+                .map(|i| PixelStats {
+                    count: i as u32,
+                    mean: values[(i + 4) % 6],
+                    m2: values[(i + 5) % 6],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round-trip.ckpt");
+        let saved = checkpoint();
+        saved.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded, saved);
+        // Compare bits so that signed zeros are distinguished
+        let bits = |c: &Checkpoint| -> Vec<u64> {
+            let film = c
+                .film
+                .iter()
+                .flat_map(|p| [p.sum.x, p.sum.y, p.sum.z, p.weight].map(f64::to_bits));
+            let stats = c
+                .stats
+                .iter()
+                .flat_map(|s| [s.mean, s.m2].map(f64::to_bits));
+            film.chain(stats).collect()
+        };
+        assert_eq!(bits(&loaded), bits(&saved));
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let path = temp_path("invalid.ckpt");
+        checkpoint().save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let error = |bytes: &[u8]| {
+            fs::write(&path, bytes).unwrap();
+            format!("{:#}", Checkpoint::load(&path).unwrap_err())
+        };
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[7] = b'1';
+        assert!(error(&wrong_magic).ends_with("not a checkpoint file"));
+
+        assert!(error(&bytes[..bytes.len() - 1]).contains("does not fit"));
+        assert!(error(&bytes[..20]).contains("failed to fill whole buffer"));
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(error(&extra).contains("does not fit"));
+
+        // Huge dimensions are rejected without reading the pixels
+        let mut huge = bytes;
+        huge[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(error(&huge).contains("4294967295 x 2 image"));
+        huge[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(error(&huge).contains("4294967295 x 4294967295 image"));
+    }
+
+    #[test]
+    fn resume_checks_settings_and_scene() {
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            2.0,
+            0.0,
+            1.0,
+        );
+        let settings = RenderSettings {
+            width: 4,
+            height: 2,
+            samples_per_pixel: 1,
+            tile_size: 2,
+            ..RenderSettings::default()
+        };
+        let world: Vec<Arc<dyn Surface>> = Vec::new();
+        let renderer = Renderer::new(camera, world, Box::new(Black), settings);
+        let saved = renderer.start_progressive().checkpoint(42);
+
+        assert!(renderer.resume_progressive(saved.clone(), 42).is_ok());
+        let error = |checkpoint: Checkpoint, scene_hash: u64| {
+            renderer
+                .resume_progressive(checkpoint, scene_hash)
+                .err()
+                .expect("checkpoint should be rejected")
+                .to_string()
+        };
+        assert_eq!(
+            error(saved.clone(), 43),
+            "checkpoint was rendered from a different scene"
+        );
+        let other_settings = Checkpoint {
+            settings_hash: saved.settings_hash + 1,
+            ..saved.clone()
+        };
+        assert_eq!(
+            error(other_settings, 42),
+            "checkpoint was rendered with different settings"
+        );
+        let other_size = Checkpoint {
+            width: 2,
+            film: saved.film[..4].to_vec(),
+            stats: saved.stats[..4].to_vec(),
+            ..saved
+        };
+        assert_eq!(
+            error(other_size, 42),
+            "checkpoint is for a 2 x 2 image, not 4 x 2"
+        );
+    }
+}
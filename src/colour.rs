@@ -1,6 +1,6 @@
 use crate::vec3::Vec3;
 use image::Rgb;
-use rand;
+use rand::Rng;
 
 /// An RGB colour
 pub type Colour = Vec3<f64>;
@@ -25,13 +25,18 @@ impl From<Colour> for Rgb<u8> {
     }
 }
 
-pub fn random() -> Colour {
-    let (r, g, b) = rand::random();
+/// Relative luminance of a linear RGB colour
+pub fn luminance(c: Colour) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Colour {
+    let (r, g, b) = rng.gen();
     Colour::new(r, g, b)
 }
 
-pub fn random_range(low: f64, high: f64) -> Colour {
-    let (r, g, b) = rand::random::<(f64, f64, f64)>();
+pub fn random_range<R: Rng + ?Sized>(rng: &mut R, low: f64, high: f64) -> Colour {
+    let (r, g, b) = rng.gen::<(f64, f64, f64)>();
     let range = high - low;
     Colour::new(r * range + low, g * range + low, b * range + low)
 }
@@ -0,0 +1,43 @@
+use crate::colour::Colour;
+use crate::environments::{Environment, EnvironmentSample};
+use crate::vec3::VecR3;
+
+/// An environment with the same colour in every direction
+#[derive(Debug, Clone)]
+pub struct Constant {
+    pub colour: Colour,
+}
+
+impl Constant {
+    pub fn new(colour: Colour) -> Self {
+        Self { colour }
+    }
+}
+
+impl Environment for Constant {
+    fn radiance(&self, _direction: &VecR3) -> Colour {
+        self.colour
+    }
+}
+
+/// An environment that emits no light
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Black;
+
+impl Environment for Black {
+    fn radiance(&self, _direction: &VecR3) -> Colour {
+        Colour::new(0.0, 0.0, 0.0)
+    }
+
+    fn emits_light(&self) -> bool {
+        false
+    }
+
+    fn sample(&self, _u: (f64, f64)) -> Option<EnvironmentSample> {
+        None
+    }
+
+    fn pdf(&self, _direction: &VecR3) -> f64 {
+        0.0
+    }
+}
@@ -0,0 +1,34 @@
+use crate::colour::Colour;
+use crate::environments::Environment;
+use crate::vec3::VecR3;
+
+/// A vertical gradient between two colours
+///
+/// Blends linearly in the Y component of the unit direction
+/// from `bottom` (looking straight down) to `top` (looking straight up).
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    pub bottom: Colour,
+    pub top: Colour,
+}
+
+impl Gradient {
+    pub fn new(bottom: Colour, top: Colour) -> Self {
+        Self { bottom, top }
+    }
+}
+
+/// A white to sky blue gradient
+impl Default for Gradient {
+    fn default() -> Self {
+        Self::new(Colour::new(1.0, 1.0, 1.0), Colour::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: &VecR3) -> Colour {
+        let unit_direction = direction.as_unit();
+        let t = (unit_direction.y + 1.0) / 2.0;
+        self.bottom * (1.0 - t) + self.top * t
+    }
+}
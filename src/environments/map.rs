@@ -0,0 +1,215 @@
+use crate::colour::{luminance, Colour};
+use crate::environments::{Environment, EnvironmentSample};
+use crate::loaders::{load_float_image, FloatImage};
+use crate::sampling::Distribution2D;
+use crate::vec3::{Vec3, VecR3};
+use std::f64::consts::PI;
+use std::path::Path;
+
+/// An environment lit by an equirectangular (latitude-longitude) image
+///
+/// The top row of the image is seen looking straight up (+Y) and the bottom row looking
+/// straight down. The horizontal centre of the image is seen looking towards -Z.
+///
+/// Directions are importance-sampled in proportion to the luminance of the image
+/// so that small bright regions such as the sun are found efficiently.
+pub struct EnvironmentMap {
+    image: FloatImage,
+    intensity: f64,
+    /// Sine and cosine of the rotation about the Y axis
+    rotation: (f64, f64),
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    /// Create an environment map from an equirectangular image.
+    pub fn new(image: FloatImage) -> Self {
+        let (width, height) = (image.width, image.height);
+        let mut function = Vec::with_capacity(width * height);
+        for y in 0..height {
+            // Weight by the solid angle of each row, which shrinks towards the poles
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            for x in 0..width {
+                function.push(luminance(image.get(x, y)) * sin_theta);
+            }
+        }
+        let distribution = Distribution2D::new(&function, width, height);
+        Self {
+            image,
+            intensity: 1.0,
+            rotation: (0.0, 1.0),
+            distribution,
+        }
+    }
+
+    /// Load an environment map from a Radiance `.hdr` or OpenEXR `.exr` image.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Ok(Self::new(load_float_image(path)?))
+    }
+
+    /// Rotate the environment counter-clockwise about the Y axis by an angle in degrees.
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees.to_radians().sin_cos();
+        self
+    }
+
+    /// Scale the brightness of the environment.
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Rotate a direction from world space into image space.
+    fn to_local(&self, v: &VecR3) -> VecR3 {
+        let (sin, cos) = self.rotation;
+        Vec3::new(v.x * cos - v.z * sin, v.y, v.x * sin + v.z * cos)
+    }
+
+    /// Rotate a direction from image space into world space.
+    fn to_world(&self, v: &VecR3) -> VecR3 {
+        let (sin, cos) = self.rotation;
+        Vec3::new(v.x * cos + v.z * sin, v.y, -v.x * sin + v.z * cos)
+    }
+
+    /// Image radiance at texture coordinates in [0, 1)^2
+    fn lookup(&self, (u, v): (f64, f64)) -> Colour {
+        let x = ((u * self.image.width as f64) as usize).min(self.image.width - 1);
+        let y = ((v * self.image.height as f64) as usize).min(self.image.height - 1);
+        self.image.get(x, y) * self.intensity
+    }
+}
+
+/// Texture coordinates of a unit direction and the sine of its polar angle.
+fn direction_to_uv(d: &VecR3) -> ((f64, f64), f64) {
+    let theta = d.y.clamp(-1.0, 1.0).acos();
+    let phi = d.x.atan2(-d.z);
+    ((0.5 + phi / (2.0 * PI), theta / PI), theta.sin())
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: &VecR3) -> Colour {
+        let (uv, _) = direction_to_uv(&self.to_local(&direction.as_unit()));
+        self.lookup(uv)
+    }
+
+    fn sample(&self, u: (f64, f64)) -> Option<EnvironmentSample> {
+        let ((s, t), pdf_uv) = self.distribution.sample_continuous(u);
+        if pdf_uv == 0.0 {
+            return None;
+        }
+        let theta = t * PI;
+        let phi = (s - 0.5) * 2.0 * PI;
+        let sin_theta = theta.sin();
+        if sin_theta == 0.0 {
+            return None;
+        }
+        let local = Vec3::new(sin_theta * phi.sin(), theta.cos(), -sin_theta * phi.cos());
+        Some(EnvironmentSample {
+            direction: self.to_world(&local),
+            radiance: self.lookup((s, t)),
+            // Change of variables from the unit square to the sphere
+            pdf: pdf_uv / (2.0 * PI * PI * sin_theta),
+        })
+    }
+
+    fn pdf(&self, direction: &VecR3) -> f64 {
+        let (uv, sin_theta) = direction_to_uv(&self.to_local(&direction.as_unit()));
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(uv) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::{uniform_sphere, uniform_sphere_pdf};
+
+    fn image(width: usize, height: usize, pixel: impl Fn(usize, usize) -> Colour) -> FloatImage {
+        FloatImage {
+            width,
+            height,
+            pixels: (0..width * height)
+                .map(|i| pixel(i % width, i / width))
+                .collect(),
+        }
+    }
+
+    /// A map with a range of brightnesses, some black pixels and a small bright sun
+    fn varied_map() -> EnvironmentMap {
+        EnvironmentMap::new(image(16, 8, |x, y| {
+            if (x, y) == (11, 2) {
+                Colour::new(500.0, 400.0, 300.0)
+            } else if (x + y) % 5 == 0 {
+                Colour::new(0.0, 0.0, 0.0)
+            } else {
+                Colour::new(x as f64, y as f64, 1.0)
+            }
+        }))
+    }
+
+    #[test]
+    fn pdf_matches_samples() {
+        for &degrees in &[0.0, 73.0] {
+            let map = varied_map().with_rotation(degrees).with_intensity(2.0);
+            for i in 0..40 {
+                for j in 0..40 {
+                    let u = ((i as f64 + 0.5) / 40.0, (j as f64 + 0.5) / 40.0);
+                    let sample = map.sample(u).unwrap();
+                    assert!((sample.direction.norm() - 1.0).abs() < 1e-12);
+                    let pdf = map.pdf(&sample.direction);
+                    assert!(
+                        (pdf - sample.pdf).abs() < 1e-9 * pdf,
+                        "{} != {}",
+                        pdf,
+                        sample.pdf
+                    );
+                    assert_eq!(sample.radiance, map.radiance(&sample.direction));
+                    assert!(sample.radiance != Colour::new(0.0, 0.0, 0.0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let map = varied_map().with_rotation(30.0);
+        let n = 400;
+        // Stratified uniform directions over the sphere
+        let integral: f64 = (0..n * n)
+            .map(|i| {
+                let u = (
+                    ((i / n) as f64 + 0.5) / n as f64,
+                    ((i % n) as f64 + 0.5) / n as f64,
+                );
+                map.pdf(&uniform_sphere(u)) / uniform_sphere_pdf()
+            })
+            .sum::<f64>()
+            / (n * n) as f64;
+        assert!((integral - 1.0).abs() < 1e-2, "{}", integral);
+    }
+
+    #[test]
+    fn constant_map_is_sampled_uniformly() {
+        let height = 32;
+        let map = EnvironmentMap::new(image(64, height, |_, _| Colour::new(0.5, 0.5, 0.5)))
+            .with_rotation(45.0);
+        // Rows are weighted by the sine of the polar angle at their centres,
+        // so the density is exactly uniform there up to the normalization of the rows
+        for row in 0..height {
+            let theta = PI * (row as f64 + 0.5) / height as f64;
+            for k in 0..10 {
+                let phi = k as f64 * 0.6;
+                let d = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let pdf = map.pdf(&d);
+                assert!((pdf * 4.0 * PI - 1.0).abs() < 1e-3, "{}", pdf * 4.0 * PI);
+                assert_eq!(map.radiance(&d), Colour::new(0.5, 0.5, 0.5));
+            }
+        }
+    }
+}
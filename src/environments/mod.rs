@@ -0,0 +1,63 @@
+pub mod constant;
+pub mod gradient;
+pub mod map;
+
+pub use constant::{Black, Constant};
+pub use gradient::Gradient;
+pub use map::EnvironmentMap;
+
+use crate::colour::Colour;
+use crate::sampling::{uniform_sphere, uniform_sphere_pdf};
+use crate::vec3::VecR3;
+
+/// A direction sampled from an environment
+#[derive(Debug, Clone, Copy)]
+pub struct EnvironmentSample {
+    /// Unit direction from which the light arrives
+    pub direction: VecR3,
+    /// Radiance arriving from `direction`
+    pub radiance: Colour,
+    /// Probability density of the sample with respect to solid angle
+    pub pdf: f64,
+}
+
+/// Light arriving from infinitely far away
+///
+/// Seen by rays that escape the scene without hitting any surface.
+pub trait Environment: Sync + Send {
+    /// Radiance arriving from a direction
+    ///
+    /// # Arguments
+    ///
+    /// * `direction` - Direction of a ray leaving the scene. Need not be a unit vector.
+    fn radiance(&self, direction: &VecR3) -> Colour;
+
+    /// Whether any light arrives from the environment.
+    fn emits_light(&self) -> bool {
+        true
+    }
+
+    /// Sample a direction from which light arrives
+    ///
+    /// The default implementation samples uniformly over the sphere of directions.
+    ///
+    /// # Arguments
+    ///
+    /// * `u` - Uniform random numbers in [0, 1).
+    ///
+    /// # Returns
+    /// None if the environment emits no light.
+    fn sample(&self, u: (f64, f64)) -> Option<EnvironmentSample> {
+        let direction = uniform_sphere(u);
+        Some(EnvironmentSample {
+            direction,
+            radiance: self.radiance(&direction),
+            pdf: uniform_sphere_pdf(),
+        })
+    }
+
+    /// Probability density with respect to solid angle of `sample` producing a direction
+    fn pdf(&self, _direction: &VecR3) -> f64 {
+        uniform_sphere_pdf()
+    }
+}
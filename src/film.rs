@@ -0,0 +1,506 @@
+use crate::colour::Colour;
+use crate::render::Framebuffer;
+use anyhow::{anyhow, Context};
+use std::f64::consts::PI;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// A pixel reconstruction filter
+///
+/// Filters are separable: the weight of a sample at offset (dx, dy) from a pixel centre
+/// is `evaluate(dx) * evaluate(dy)`. Offsets are measured in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Equal weight within `radius`; with radius 0.5 each sample only affects its own pixel.
+    Box { radius: f64 },
+    /// Weight decreasing linearly to zero at `radius`.
+    Tent { radius: f64 },
+    /// A Gaussian with standard deviation `sigma`, shifted to reach zero at `radius`.
+    Gaussian { radius: f64, sigma: f64 },
+    /// The Mitchell-Netravali cubic with parameters `b` and `c`.
+    Mitchell { radius: f64, b: f64, c: f64 },
+    /// A sinc windowed by a wider sinc that reaches zero at `radius`.
+    Lanczos { radius: f64 },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    /// Distance from the pixel centre beyond which the weight is zero
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => radius,
+        }
+    }
+
+    /// Weight of a sample at offset `(dx, dy)` from a pixel centre
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate(dx) * self.evaluate(dy)
+    }
+
+    /// One-dimensional filter weight at offset `d`
+    pub fn evaluate(&self, d: f64) -> f64 {
+        match *self {
+            // Half-open so that a sample on a pixel boundary counts exactly once
+            Filter::Box { radius } => {
+                if -radius <= d && d < radius {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Tent { radius } => (radius - d.abs()).max(0.0),
+            Filter::Gaussian { radius, sigma } => {
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(d) - gaussian(radius)).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => mitchell(2.0 * d / radius, b, c),
+            Filter::Lanczos { radius } => {
+                if d.abs() >= radius {
+                    0.0
+                } else {
+                    sinc(d) * sinc(d / radius)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    /// Parse a filter name with an optional radius in pixels, such as "gaussian:1.5".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let radius = match parts.next() {
+            Some(r) => Some(
+                r.parse::<f64>()
+                    .with_context(|| format!("invalid filter radius '{}'", r))?,
+            ),
+            None => None,
+        };
+        if let Some(r) = radius {
+            if r <= 0.0 {
+                return Err(anyhow!("filter radius must be positive; got {}", r));
+            }
+        }
+        match name {
+            "box" => Ok(Filter::Box {
+                radius: radius.unwrap_or(0.5),
+            }),
+            "tent" => Ok(Filter::Tent {
+                radius: radius.unwrap_or(1.0),
+            }),
+            "gaussian" => {
+                let radius = radius.unwrap_or(1.5);
+                Ok(Filter::Gaussian {
+                    radius,
+                    sigma: radius / 3.0,
+                })
+            }
+            "mitchell" => Ok(Filter::Mitchell {
+                radius: radius.unwrap_or(2.0),
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            }),
+            "lanczos" => Ok(Filter::Lanczos {
+                radius: radius.unwrap_or(3.0),
+            }),
+            _ => Err(anyhow!(
+                "unknown filter '{}'; expected box, tent, gaussian, mitchell or lanczos",
+                name
+            )),
+        }
+    }
+}
+
+/// The Mitchell-Netravali cubic, which is non-zero for |x| < 2
+fn mitchell(x: f64, b: f64, c: f64) -> f64 {
+    let x = x.abs();
+    if x < 1.0 {
+        ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b))
+            / 6.0
+    } else if x < 2.0 {
+        ((-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        0.0
+    }
+}
+
+/// Normalized sinc function
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    let px = PI * x;
+    px.sin() / px
+}
+
+/// The filter-weighted sum of the samples contributing to a pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilmPixel {
+    pub sum: Colour,
+    pub weight: f64,
+    /// Number of samples taken within the pixel
+    pub samples: u32,
+}
+
+impl Default for FilmPixel {
+    fn default() -> Self {
+        Self {
+            sum: Colour::new(0.0, 0.0, 0.0),
+            weight: 0.0,
+            samples: 0,
+        }
+    }
+}
+
+/// Total filter weight below which a pixel is treated as having no samples
+const MIN_PIXEL_WEIGHT: f64 = 1e-6;
+
+/// Accumulates radiance samples into an image through a reconstruction filter
+///
+/// Samples may contribute to several neighbouring pixels.
+/// The film can be shared between threads; samples are either added directly with
+/// `add_sample` or collected without locking in a `FilmTile` and merged with `merge_tile`.
+pub struct Film {
+    width: u32,
+    height: u32,
+    filter: Filter,
+    pixels: Mutex<Vec<FilmPixel>>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32, filter: Filter) -> Self {
+        Self {
+            width,
+            height,
+            filter,
+            pixels: Mutex::new(vec![FilmPixel::default(); (width * height) as usize]),
+        }
+    }
+
+    /// Create a film holding previously accumulated pixels.
+    ///
+    /// # Arguments
+    /// * `pixels` - Row-major pixels starting from the top left, as returned by `pixels`.
+    pub fn from_pixels(width: u32, height: u32, filter: Filter, pixels: Vec<FilmPixel>) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize);
+        Self {
+            width,
+            height,
+            filter,
+            pixels: Mutex::new(pixels),
+        }
+    }
+
+    /// A copy of the accumulated pixels
+    pub fn pixels(&self) -> Vec<FilmPixel> {
+        self.pixels.lock().unwrap().clone()
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// Create an empty tile for samples taken within a range of pixels.
+    ///
+    /// The tile also covers the surrounding pixels reached by the filter.
+    pub fn tile(&self, xs: Range<u32>, ys: Range<u32>) -> FilmTile {
+        let radius = self.filter.radius();
+        let margin = radius.ceil() as u32;
+        let x0 = xs.start.saturating_sub(margin);
+        let y0 = ys.start.saturating_sub(margin);
+        let x1 = (xs.end + margin).min(self.width);
+        let y1 = (ys.end + margin).min(self.height);
+        FilmTile {
+            x0,
+            y0,
+            width: x1 - x0,
+            height: y1 - y0,
+            filter: self.filter,
+            pixels: vec![FilmPixel::default(); ((x1 - x0) * (y1 - y0)) as usize],
+        }
+    }
+
+    /// Add the samples of a tile to the film.
+    pub fn merge_tile(&self, tile: &FilmTile) {
+        let mut pixels = self.pixels.lock().unwrap();
+        for ty in 0..tile.height {
+            for tx in 0..tile.width {
+                let source = tile.pixels[(ty * tile.width + tx) as usize];
+                let target = &mut pixels[((tile.y0 + ty) * self.width + tile.x0 + tx) as usize];
+                target.sum += source.sum;
+                target.weight += source.weight;
+                target.samples += source.samples;
+            }
+        }
+    }
+
+    /// Add a single sample to the film.
+    ///
+    /// # Arguments
+    /// * `position` - Continuous raster position of the sample;
+    ///   pixel (x, y) covers [x, x + 1) × [y, y + 1) with y increasing downwards.
+    /// * `colour` - Radiance of the sample.
+    pub fn add_sample(&self, position: (f64, f64), colour: Colour) {
+        let mut pixels = self.pixels.lock().unwrap();
+        splat(
+            &self.filter,
+            (0, 0, self.width, self.height),
+            &mut pixels,
+            position,
+            colour,
+        );
+    }
+
+    /// The reconstructed image
+    ///
+    /// Filters with negative lobes can leave a pixel with a tiny or negative total weight;
+    /// such pixels are left black and negative values are clamped to zero.
+    pub fn to_framebuffer(&self) -> Framebuffer {
+        let pixels = self.pixels.lock().unwrap();
+        let mut framebuffer = Framebuffer::new(self.width, self.height);
+        let black = Colour::new(0.0, 0.0, 0.0);
+        for (i, pixel) in pixels.iter().enumerate() {
+            if pixel.weight > MIN_PIXEL_WEIGHT {
+                framebuffer.pixels[i] = (pixel.sum / pixel.weight).max(black);
+            }
+            framebuffer.samples[i] = pixel.samples;
+        }
+        framebuffer
+    }
+}
+
+/// Samples for a rectangular region of a `Film`, collected by a single thread
+pub struct FilmTile {
+    x0: u32,
+    y0: u32,
+    width: u32,
+    height: u32,
+    filter: Filter,
+    pixels: Vec<FilmPixel>,
+}
+
+impl FilmTile {
+    /// Add a sample to the tile.
+    ///
+    /// # Arguments
+    /// * `position` - Continuous raster position of the sample in film coordinates.
+    /// * `colour` - Radiance of the sample.
+    #[inline]
+    pub fn add_sample(&mut self, position: (f64, f64), colour: Colour) {
+        splat(
+            &self.filter,
+            (self.x0, self.y0, self.width, self.height),
+            &mut self.pixels,
+            position,
+            colour,
+        );
+    }
+}
+
+/// Add a sample to the pixels of a rectangle weighted by the filter.
+///
+/// # Arguments
+/// * `bounds` - Film coordinates of the top left pixel of `pixels`, and its width and height.
+#[inline]
+fn splat(
+    filter: &Filter,
+    (x0, y0, width, height): (u32, u32, u32, u32),
+    pixels: &mut [FilmPixel],
+    (px, py): (f64, f64),
+    colour: Colour,
+) {
+    let (sx, sy) = (px.floor(), py.floor());
+    if sx >= x0 as f64 && sx < (x0 + width) as f64 && sy >= y0 as f64 && sy < (y0 + height) as f64 {
+        pixels[((sy as u32 - y0) * width + sx as u32 - x0) as usize].samples += 1;
+    }
+
+    let radius = filter.radius();
+    // Pixel centres are at half-integer positions
+    let (cx, cy) = (px - 0.5, py - 0.5);
+    let x_start = ((cx - radius).ceil().max(x0 as f64)) as u32;
+    let y_start = ((cy - radius).ceil().max(y0 as f64)) as u32;
+    let x_end = ((cx + radius).floor() + 1.0).min((x0 + width) as f64);
+    let y_end = ((cy + radius).floor() + 1.0).min((y0 + height) as f64);
+    for y in y_start..(y_end.max(0.0) as u32) {
+        let wy = filter.evaluate(cy - y as f64);
+        if wy == 0.0 {
+            continue;
+        }
+        for x in x_start..(x_end.max(0.0) as u32) {
+            let weight = wy * filter.evaluate(cx - x as f64);
+            if weight == 0.0 {
+                continue;
+            }
+            let pixel = &mut pixels[((y - y0) * width + x - x0) as usize];
+            pixel.sum += colour * weight;
+            pixel.weight += weight;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::SampleRng;
+    use crate::tiles::{tiles, TileOrder};
+    use rand::{Rng, SeedableRng};
+
+    const FILTERS: [&str; 7] = [
+        "box", "box:1.3", "tent", "tent:2", "gaussian", "mitchell", "lanczos",
+    ];
+
+    fn random_samples(rng: &mut SampleRng, width: u32, height: u32) -> Vec<((f64, f64), Colour)> {
+        (0..500)
+            .map(|_| {
+                let position = (
+                    rng.gen_range(0.0..width as f64),
+                    rng.gen_range(0.0..height as f64),
+                );
+                let colour = Colour::new(rng.gen(), rng.gen(), rng.gen());
+                (position, colour)
+            })
+            .collect()
+    }
+
+    fn assert_pixels_close(a: &[FilmPixel], b: &[FilmPixel]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert!((a.sum - b.sum).norm() < 1e-9, "{:?} != {:?}", a, b);
+            assert!((a.weight - b.weight).abs() < 1e-9, "{:?} != {:?}", a, b);
+            assert_eq!(a.samples, b.samples);
+        }
+    }
+
+    #[test]
+    fn filters_are_symmetric_with_finite_integrals() {
+        for name in FILTERS {
+            let filter = Filter::from_str(name).unwrap();
+            let radius = filter.radius();
+            for i in 0..=1000 {
+                let d = i as f64 / 1000.0 * 1.2 * radius;
+                let weight = filter.evaluate(d);
+                if d < radius {
+                    // The box filter is half-open at its edge
+                    assert_eq!(weight, filter.evaluate(-d), "{} at {}", name, d);
+                } else {
+                    assert_eq!(weight, 0.0, "{} at {}", name, d);
+                }
+            }
+            // Midpoint rule over the support
+            let n = 100_000;
+            let step = 2.0 * radius / n as f64;
+            let integral: f64 = (0..n)
+                .map(|i| filter.evaluate(-radius + (i as f64 + 0.5) * step) * step)
+                .sum();
+            assert!(integral > 0.0 && integral.is_finite(), "{}", name);
+            let expected = match filter {
+                Filter::Box { radius } => Some(2.0 * radius),
+                Filter::Tent { radius } => Some(radius * radius),
+                Filter::Mitchell { .. } => Some(1.0),
+                _ => None,
+            };
+            if let Some(expected) = expected {
+                assert!((integral - expected).abs() < 1e-4, "{}: {}", name, integral);
+            }
+        }
+    }
+
+    #[test]
+    fn unit_filters_sum_to_one_over_pixels() {
+        // These filters weight a sample equally wherever it falls between pixel centres
+        for name in ["box", "tent", "mitchell"] {
+            let filter = Filter::from_str(name).unwrap();
+            for i in 0..100 {
+                let offset = i as f64 / 100.0;
+                let sum: f64 = (-3..=3).map(|k| filter.evaluate(offset + k as f64)).sum();
+                assert!((sum - 1.0).abs() < 1e-12, "{} at {}: {}", name, offset, sum);
+            }
+        }
+    }
+
+    #[test]
+    fn box_filter_averages_samples_in_each_pixel() {
+        let (width, height) = (4, 3);
+        let film = Film::new(width, height, Filter::default());
+        let mut sums = vec![Colour::new(0.0, 0.0, 0.0); (width * height) as usize];
+        let mut counts = vec![0; (width * height) as usize];
+        let mut rng = SampleRng::seed_from_u64(1);
+        for (position, colour) in random_samples(&mut rng, width, height) {
+            film.add_sample(position, colour);
+            let i = (position.1 as u32 * width + position.0 as u32) as usize;
+            sums[i] += colour;
+            counts[i] += 1;
+        }
+        let framebuffer = film.to_framebuffer();
+        for i in 0..sums.len() {
+            assert!(counts[i] > 0);
+            let average = sums[i] / counts[i] as f64;
+            assert!((framebuffer.pixels[i] - average).norm() < 1e-12);
+            assert_eq!(framebuffer.samples[i], counts[i]);
+        }
+    }
+
+    #[test]
+    fn merged_tiles_match_direct_splatting() {
+        let (width, height) = (13, 9);
+        let mut rng = SampleRng::seed_from_u64(2);
+        for name in FILTERS.iter().chain(&["lanczos:4.5"]) {
+            let filter = Filter::from_str(name).unwrap();
+            let samples = random_samples(&mut rng, width, height);
+            let direct = Film::new(width, height, filter);
+            for &(position, colour) in &samples {
+                direct.add_sample(position, colour);
+            }
+
+            let merged = Film::new(width, height, filter);
+            for tile in tiles(width, height, 4, TileOrder::Hilbert) {
+                let mut film_tile = merged.tile(tile.xs.clone(), tile.ys.clone());
+                for &(position, colour) in &samples {
+                    let (x, y) = (position.0 as u32, position.1 as u32);
+                    if tile.xs.contains(&x) && tile.ys.contains(&y) {
+                        film_tile.add_sample(position, colour);
+                    }
+                }
+                merged.merge_tile(&film_tile);
+            }
+            assert_pixels_close(&merged.pixels(), &direct.pixels());
+        }
+    }
+
+    #[test]
+    fn negative_weights_do_not_produce_negative_pixels() {
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let film = Film::new(4, 1, Filter::from_str("mitchell").unwrap());
+        // 1.5 pixels from the centre of pixel 0, in the negative lobe of the filter
+        film.add_sample((2.0, 0.5), Colour::new(10.0, 10.0, 10.0));
+        // Only a negative weight; the pixel is left black
+        assert_eq!(film.to_framebuffer().pixels[0], black);
+
+        // A dim sample with a larger positive weight leaves a negative weighted sum
+        film.add_sample((1.45, 0.5), Colour::new(1.0, 1.0, 1.0));
+        let framebuffer = film.to_framebuffer();
+        assert_eq!(framebuffer.pixels[0], black);
+        for pixel in &framebuffer.pixels {
+            assert!(pixel.x >= 0.0 && pixel.x.is_finite());
+        }
+    }
+}
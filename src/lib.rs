@@ -1,17 +1,40 @@
+pub mod aabb;
+pub mod bvh;
 pub mod camera;
+pub mod checkpoint;
 pub mod colour;
+pub mod environments;
+pub mod film;
+pub mod lights;
+pub mod loaders;
 pub mod materials;
 pub mod objects;
+pub mod output;
 pub mod ratio;
 pub mod ray;
+pub mod render;
+pub mod roots;
+pub mod samplers;
+pub mod sampling;
+pub mod scene;
+pub mod sdf;
 pub mod surface;
 pub mod threadpool;
+pub mod tiles;
+pub mod tone_mapping;
+pub mod transform;
 pub mod vec3;
 
+pub use aabb::Aabb;
+pub use bvh::Bvh;
 pub use camera::Camera;
 pub use colour::Colour;
+pub use environments::Environment;
 pub use materials::Material;
 pub use ratio::Ratio;
 pub use ray::{Ray, RayR3};
+pub use render::{RenderSettings, Renderer};
+pub use scene::Scene;
 pub use surface::Surface;
+pub use transform::{Matrix4, Transform};
 pub use vec3::{Vec3, VecR3};
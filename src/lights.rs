@@ -0,0 +1,167 @@
+use crate::colour::Colour;
+use crate::environments::Environment;
+use crate::materials::Material;
+use crate::surface::Surface;
+use crate::vec3::VecR3;
+use std::collections::HashSet;
+use std::f64;
+use std::sync::Arc;
+
+/// A direction sampled towards a light source
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// Unit direction from the reference point towards the light
+    pub direction: VecR3,
+    /// Distance from the reference point to the sampled point; infinite for environments.
+    pub distance: f64,
+    /// Radiance emitted by the light towards the reference point
+    pub radiance: Colour,
+    /// Probability density of the sample with respect to solid angle
+    pub pdf: f64,
+}
+
+/// A direction sample towards a point chosen uniformly by area on a flat light.
+///
+/// # Arguments
+/// * `origin` - The point from which the light is seen.
+/// * `point` - The sampled point on the light.
+/// * `normal` - Unit normal of the light pointing from its front face.
+/// * `area` - Total area of the light.
+/// * `material` - Material of the light.
+///
+/// # Returns
+/// None if the light is seen edge-on from `origin`.
+pub(crate) fn sample_flat_light(
+    origin: &VecR3,
+    point: VecR3,
+    normal: VecR3,
+    area: f64,
+    material: &dyn Material,
+) -> Option<LightSample> {
+    let offset = point - *origin;
+    let distance = offset.norm();
+    if area == 0.0 || distance == 0.0 {
+        return None;
+    }
+    let direction = offset / distance;
+    let cos_light = direction.dot(normal);
+    if cos_light == 0.0 {
+        return None;
+    }
+    Some(LightSample {
+        direction,
+        distance,
+        radiance: material.emitted(&point, cos_light < 0.0),
+        pdf: flat_light_pdf(distance, cos_light, area),
+    })
+}
+
+/// Solid angle density of a point sampled uniformly by area on a flat light.
+///
+/// # Arguments
+/// * `distance` - Distance from the reference point to the sampled point.
+/// * `cos_light` - Cosine of the angle between the light normal and the direction.
+/// * `area` - Total area of the light.
+pub(crate) fn flat_light_pdf(distance: f64, cos_light: f64, area: f64) -> f64 {
+    // Change of variables from area to solid angle
+    distance * distance / (cos_light.abs() * area)
+}
+
+/// The light sources of a scene that support explicit sampling
+///
+/// Lights are chosen uniformly at random from the emissive surfaces and the environment.
+pub struct LightList {
+    surfaces: Vec<Arc<dyn Surface>>,
+    /// Addresses of the surfaces in `surfaces`
+    addresses: HashSet<usize>,
+    /// Whether the environment is sampled
+    environment: bool,
+}
+
+/// The address of a surface, used to identify it.
+pub(crate) fn address(surface: &dyn Surface) -> usize {
+    surface as *const dyn Surface as *const () as usize
+}
+
+impl LightList {
+    /// Collect the light sources of a scene.
+    pub fn new(world: &dyn Surface, environment: &dyn Environment) -> Self {
+        let surfaces = world.lights();
+        let addresses = surfaces.iter().map(|s| address(&**s)).collect();
+        Self {
+            surfaces,
+            addresses,
+            environment: environment.emits_light(),
+        }
+    }
+
+    /// Number of lights, including the environment if it is sampled
+    pub fn len(&self) -> usize {
+        self.surfaces.len() + self.environment as usize
+    }
+
+    /// Whether there are no lights to sample
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sample a direction towards a light as seen from `origin`.
+    ///
+    /// # Arguments
+    /// * `origin` - The point from which the light is seen.
+    /// * `environment` - The scene environment.
+    /// * `u_select` - A uniform random number in [0, 1) used to select a light.
+    /// * `u` - Uniform random numbers in [0, 1) used to sample the selected light.
+    ///
+    /// # Returns
+    /// The sampled direction, with a density that includes the probability of selecting the
+    /// light. None if no sample could be generated.
+    pub fn sample(
+        &self,
+        origin: &VecR3,
+        environment: &dyn Environment,
+        u_select: f64,
+        u: (f64, f64),
+    ) -> Option<LightSample> {
+        let n = self.len();
+        if n == 0 {
+            return None;
+        }
+        let index = ((u_select * n as f64) as usize).min(n - 1);
+        let mut sample = match self.surfaces.get(index) {
+            Some(surface) => surface.sample(origin, u)?,
+            None => {
+                let sample = environment.sample(u)?;
+                LightSample {
+                    direction: sample.direction,
+                    distance: f64::INFINITY,
+                    radiance: sample.radiance,
+                    pdf: sample.pdf,
+                }
+            }
+        };
+        if sample.pdf <= 0.0 {
+            return None;
+        }
+        sample.pdf /= n as f64;
+        Some(sample)
+    }
+
+    /// Probability density with which `sample` produces a direction towards a surface.
+    ///
+    /// Zero if the surface is not one of the lights or cannot be sampled from `origin`.
+    pub fn pdf_surface(&self, light: &dyn Surface, origin: &VecR3, direction: &VecR3) -> f64 {
+        if !self.addresses.contains(&address(light)) {
+            return 0.0;
+        }
+        light.pdf(origin, direction) / self.len() as f64
+    }
+
+    /// Probability density with which `sample` produces a direction towards the environment.
+    pub fn pdf_environment(&self, environment: &dyn Environment, direction: &VecR3) -> f64 {
+        if !self.environment {
+            return 0.0;
+        }
+        environment.pdf(direction) / self.len() as f64
+    }
+}
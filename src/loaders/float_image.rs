@@ -0,0 +1,85 @@
+use crate::colour::Colour;
+use anyhow::{anyhow, Context};
+use exr::prelude::read_first_rgba_layer_from_file;
+use image::codecs::hdr::HdrDecoder;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// An image with floating-point linear RGB pixels
+#[derive(Debug, Clone)]
+pub struct FloatImage {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major pixels starting from the top left
+    pub pixels: Vec<Colour>,
+}
+
+impl FloatImage {
+    /// The pixel at column `x` and row `y`
+    pub fn get(&self, x: usize, y: usize) -> Colour {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Load a high dynamic range image.
+///
+/// Supports Radiance RGBE (`.hdr`) and OpenEXR (`.exr`) files, chosen by file extension.
+pub fn load_float_image<P: AsRef<Path>>(path: P) -> anyhow::Result<FloatImage> {
+    let path = path.as_ref();
+    match lowercase_extension(path).as_deref() {
+        Some("hdr") => load_hdr(path),
+        Some("exr") => load_exr(path),
+        _ => Err(anyhow!(
+            "unsupported image format '{}'; expected .hdr or .exr",
+            path.display()
+        )),
+    }
+    .with_context(|| format!("failed to load image '{}'", path.display()))
+}
+
+/// Whether a path names an image supported by `load_float_image`.
+pub fn is_float_image<P: AsRef<Path>>(path: P) -> bool {
+    matches!(
+        lowercase_extension(path.as_ref()).as_deref(),
+        Some("hdr") | Some("exr")
+    )
+}
+
+/// Lower-case extension of a path, if any
+pub(crate) fn lowercase_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+}
+
+fn load_hdr(path: &Path) -> anyhow::Result<FloatImage> {
+    let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+    let metadata = decoder.metadata();
+    let pixels = decoder
+        .read_image_hdr()?
+        .into_iter()
+        .map(|p| Colour::new(p[0] as f64, p[1] as f64, p[2] as f64))
+        .collect();
+    Ok(FloatImage {
+        width: metadata.width as usize,
+        height: metadata.height as usize,
+        pixels,
+    })
+}
+
+fn load_exr(path: &Path) -> anyhow::Result<FloatImage> {
+    let image = read_first_rgba_layer_from_file(
+        path,
+        |resolution, _channels| FloatImage {
+            width: resolution.width(),
+            height: resolution.height(),
+            pixels: vec![Colour::new(0.0, 0.0, 0.0); resolution.width() * resolution.height()],
+        },
+        |image: &mut FloatImage, position, (r, g, b, _a): (f32, f32, f32, f32)| {
+            let index = position.y() * image.width + position.x();
+            image.pixels[index] = Colour::new(r as f64, g as f64, b as f64);
+        },
+    )?;
+    Ok(image.layer_data.channel_data.pixels)
+}
@@ -0,0 +1,51 @@
+pub mod float_image;
+pub mod mtl;
+pub mod obj;
+
+pub use float_image::{is_float_image, load_float_image, FloatImage};
+pub use mtl::load_mtl;
+pub use obj::{load_obj, load_obj_with_libraries, load_obj_with_material, ObjMesh};
+
+use anyhow::{anyhow, Context};
+use std::str::FromStr;
+
+/// Parse exactly `N` whitespace-separated values from a statement's arguments.
+fn parse_args<T, const N: usize>(args: &[&str]) -> anyhow::Result<[T; N]>
+where
+    T: FromStr + Copy + Default,
+    <T as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+{
+    if args.len() != N {
+        return Err(anyhow!("expected {} values, got {}", N, args.len()));
+    }
+    let mut values = [T::default(); N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .with_context(|| format!("invalid number '{}'", arg))?;
+    }
+    Ok(values)
+}
+
+/// Split a line into a statement keyword and its arguments, ignoring comments.
+///
+/// Returns None for blank lines.
+fn split_statement(line: &str) -> Option<(&str, Vec<&str>)> {
+    let line = match line.find('#') {
+        Some(i) => &line[..i],
+        None => line,
+    };
+    let mut parts = line.split_whitespace();
+    let keyword = parts.next()?;
+    Some((keyword, parts.collect()))
+}
+
+/// Write a file for a loader test into the temporary directory.
+#[cfg(test)]
+fn temp_file(name: &str, contents: &str) -> std::path::PathBuf {
+    let directory = std::env::temp_dir().join(format!("raytracer-loaders-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
@@ -0,0 +1,230 @@
+use super::{parse_args, split_statement};
+use crate::colour::Colour;
+use crate::materials::{DiffuseLight, Lambertian, Material, Metal, Transparent};
+use anyhow::{anyhow, Context};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Material properties parsed from a Wavefront MTL file
+#[derive(Debug, Clone)]
+pub struct MtlMaterial {
+    /// Diffuse colour (`Kd`)
+    pub diffuse: Colour,
+    /// Specular colour (`Ks`)
+    pub specular: Colour,
+    /// Specular exponent (`Ns`)
+    pub specular_exponent: f64,
+    /// Index of refraction (`Ni`), if given
+    pub refractive_index: Option<f64>,
+    /// Opacity (`d`, or one minus `Tr`)
+    pub dissolve: f64,
+    /// Emitted colour (`Ke`)
+    pub emission: Colour,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            diffuse: Colour::new(0.8, 0.8, 0.8),
+            specular: Colour::new(0.0, 0.0, 0.0),
+            specular_exponent: 0.0,
+            refractive_index: None,
+            dissolve: 1.0,
+            emission: Colour::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// Convert to the closest available material
+    ///
+    /// * Materials with a non-zero emission colour become `DiffuseLight`.
+    /// * Partially transparent materials become `Transparent`.
+    /// * Materials with a specular colour brighter than the diffuse colour become `Metal`,
+    ///   with fuzz decreasing as the specular exponent increases.
+    /// * Everything else becomes `Lambertian`.
+    pub fn to_material(&self) -> Arc<dyn Material> {
+        if max_component(self.emission) > 0.0 {
+            Arc::new(DiffuseLight::new(self.emission))
+        } else if self.dissolve < 1.0 {
+            Arc::new(Transparent::new(self.refractive_index.unwrap_or(1.5)))
+        } else if max_component(self.specular) > max_component(self.diffuse) {
+            let fuzz = (2.0 / (self.specular_exponent.max(0.0) + 2.0)).sqrt();
+            Arc::new(Metal::new(self.specular, fuzz))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse))
+        }
+    }
+}
+
+fn max_component(c: Colour) -> f64 {
+    c.x.max(c.y).max(c.z)
+}
+
+/// Load the materials of a Wavefront MTL file, keyed by name.
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> anyhow::Result<HashMap<String, Arc<dyn Material>>> {
+    Ok(parse_mtl_file(path.as_ref())?
+        .into_iter()
+        .map(|(name, material)| (name, material.to_material()))
+        .collect())
+}
+
+/// Parse the materials of a Wavefront MTL file without converting them.
+pub fn parse_mtl_file(path: &Path) -> anyhow::Result<HashMap<String, MtlMaterial>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read material library '{}'", path.display()))?;
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+    for (line_index, line) in text.lines().enumerate() {
+        let (keyword, args) = match split_statement(line) {
+            Some(statement) => statement,
+            None => continue,
+        };
+        let result = (|| -> anyhow::Result<()> {
+            if keyword == "newmtl" {
+                if args.is_empty() {
+                    return Err(anyhow!("missing material name"));
+                }
+                if let Some((name, material)) = current.take() {
+                    materials.insert(name, material);
+                }
+                current = Some((args.join(" "), MtlMaterial::default()));
+                return Ok(());
+            }
+            let material = match current.as_mut() {
+                Some((_, material)) => material,
+                None => return Err(anyhow!("'{}' before any 'newmtl'", keyword)),
+            };
+            match keyword {
+                "Kd" => material.diffuse = parse_colour(&args)?,
+                "Ks" => material.specular = parse_colour(&args)?,
+                "Ke" => material.emission = parse_colour(&args)?,
+                "Ns" => material.specular_exponent = parse_args::<f64, 1>(&args)?[0],
+                "Ni" => material.refractive_index = Some(parse_args::<f64, 1>(&args)?[0]),
+                "d" => material.dissolve = parse_dissolve(&args)?,
+                "Tr" => material.dissolve = 1.0 - parse_args::<f64, 1>(&args)?[0],
+                // Ambient colour, illumination model, texture maps and other statements
+                // have no equivalent and are ignored.
+                _ => {}
+            }
+            Ok(())
+        })();
+        result.with_context(|| format!("{}:{}", path.display(), line_index + 1))?;
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+/// Parse an RGB colour, where a single value is used for all channels.
+fn parse_colour(args: &[&str]) -> anyhow::Result<Colour> {
+    if args.first() == Some(&"spectral") || args.first() == Some(&"xyz") {
+        return Err(anyhow!("only RGB colours are supported"));
+    }
+    if args.len() == 1 {
+        let [v] = parse_args::<f64, 1>(args)?;
+        return Ok(Colour::new(v, v, v));
+    }
+    let [r, g, b] = parse_args::<f64, 3>(args)?;
+    Ok(Colour::new(r, g, b))
+}
+
+/// Parse a dissolve statement, which may be prefixed by `-halo`.
+fn parse_dissolve(args: &[&str]) -> anyhow::Result<f64> {
+    let args = match args.first() {
+        Some(&"-halo") => &args[1..],
+        _ => args,
+    };
+    Ok(parse_args::<f64, 1>(args)?[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::temp_file;
+    use super::*;
+    use crate::ray::RayR3;
+    use crate::samplers::IndependentSampler;
+    use crate::vec3::Vec3;
+
+    /// Scatter a ray straight down onto a surface facing up.
+    fn scatter(material: &dyn Material) -> Colour {
+        let ray = RayR3::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut sampler = IndependentSampler::new(0);
+        let (attenuation, _) = material
+            .scatter(
+                &ray,
+                &Vec3::new(0.0, 0.0, 0.0),
+                &Vec3::new(0.0, 0.0, 1.0),
+                true,
+                &mut sampler,
+            )
+            .unwrap();
+        attenuation
+    }
+
+    #[test]
+    fn conversion() {
+        let path = temp_file(
+            "conversion.mtl",
+            "newmtl lamp\nKd 0.5 0.5 0.5\nKe 4 4 2\n\
+             newmtl glass\nKd 0.2 0.2 0.2\nd 0.25\nNi 1.33\n\
+             newmtl chrome\nKd 0.1 0.1 0.1\nKs 0.9 0.8 0.7\nNs 98\n\
+             newmtl clay\nKd 0.6 0.4 0.2\nKs 0.1\n",
+        );
+        let materials = parse_mtl_file(&path).unwrap();
+        assert_eq!(materials["glass"].dissolve, 0.25);
+        assert_eq!(materials["glass"].refractive_index, Some(1.33));
+        let materials = load_mtl(&path).unwrap();
+
+        let lamp = &materials["lamp"];
+        assert!(lamp.is_emissive());
+        assert_eq!(
+            lamp.emitted(&Vec3::new(0.0, 0.0, 0.0), true),
+            Colour::new(4.0, 4.0, 2.0)
+        );
+
+        // Transparent materials do not absorb
+        let glass = &materials["glass"];
+        assert!(!glass.is_emissive());
+        assert_eq!(scatter(&**glass), Colour::new(1.0, 1.0, 1.0));
+
+        // Metal fuzz is sqrt(2 / (Ns + 2)); directions within that radius of the mirror
+        // direction can be scattered
+        let chrome = &materials["chrome"];
+        assert_eq!(scatter(&**chrome), Colour::new(0.9, 0.8, 0.7));
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let fuzz: f64 = 0.02f64.sqrt();
+        let direction = |angle: f64| Vec3::new(angle.sin(), 0.0, angle.cos());
+        let inside = direction(0.99 * fuzz.asin());
+        let outside = direction(1.01 * fuzz.asin());
+        assert!(chrome.pdf(&inside, &normal, &normal, true) > 0.0);
+        assert_eq!(chrome.pdf(&outside, &normal, &normal, true), 0.0);
+
+        let clay = &materials["clay"];
+        assert!(!clay.is_delta());
+        assert_eq!(scatter(&**clay), Colour::new(0.6, 0.4, 0.2));
+    }
+
+    #[test]
+    fn errors_name_file_and_line() {
+        let path = temp_file("errors.mtl", "# colours\nnewmtl red\nKd 1 nope 0\n");
+        let message = format!("{:#}", parse_mtl_file(&path).unwrap_err());
+        let location = format!("{}:3: ", path.display());
+        assert!(
+            message.starts_with(&format!("{}invalid number 'nope'", location)),
+            "{}",
+            message
+        );
+
+        let path = temp_file("orphan.mtl", "Kd 1 0 0\n");
+        let message = format!("{:#}", parse_mtl_file(&path).unwrap_err());
+        assert_eq!(
+            message,
+            format!("{}:1: 'Kd' before any 'newmtl'", path.display())
+        );
+    }
+}
@@ -0,0 +1,483 @@
+use super::mtl::{parse_mtl_file, MtlMaterial};
+use super::{parse_args, split_statement};
+use crate::colour::Colour;
+use crate::materials::{Lambertian, Material};
+use crate::objects::{Face, TriangleMesh};
+use crate::vec3::{Vec3, VecR3};
+use anyhow::{anyhow, Context};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A mesh loaded from a Wavefront OBJ file
+pub struct ObjMesh {
+    /// Name of the object or group containing the faces; empty if there is none.
+    pub name: String,
+    /// Name of the MTL material used by the faces, if any.
+    pub material_name: Option<String>,
+    pub mesh: TriangleMesh<Arc<dyn Material>>,
+}
+
+/// Load the meshes of a Wavefront OBJ file along with their MTL materials.
+///
+/// Produces one mesh for each combination of object/group and material.
+/// Polygons are triangulated as fans so they must be convex.
+/// Faces without a material are given a grey `Lambertian` material.
+pub fn load_obj<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<ObjMesh>> {
+    Ok(load_obj_with_libraries(path)?.0)
+}
+
+/// Load the meshes of a Wavefront OBJ file along with their MTL materials.
+///
+/// Like `load_obj`, but also returns the paths of the material libraries that were read.
+pub fn load_obj_with_libraries<P: AsRef<Path>>(
+    path: P,
+) -> anyhow::Result<(Vec<ObjMesh>, Vec<PathBuf>)> {
+    let path = path.as_ref();
+    let obj = parse_obj_file(path)?;
+
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut library: HashMap<String, MtlMaterial> = HashMap::new();
+    let mut library_paths = Vec::new();
+    for (mtl_name, line_number) in &obj.material_libraries {
+        let mtl_path = directory.join(mtl_name);
+        let materials = parse_mtl_file(&mtl_path)
+            .with_context(|| format!("{}:{}", path.display(), line_number))?;
+        library.extend(materials);
+        library_paths.push(mtl_path);
+    }
+
+    let default_material: Arc<dyn Material> = Arc::new(Lambertian::new(Colour::new(0.8, 0.8, 0.8)));
+    let mut converted: HashMap<&str, Arc<dyn Material>> = HashMap::new();
+    for group in &obj.groups {
+        if let Some((name, line_number)) = &group.material {
+            if !converted.contains_key(name.as_str()) {
+                let material = library.get(name).ok_or_else(|| {
+                    anyhow!(
+                        "{}:{}: unknown material '{}'",
+                        path.display(),
+                        line_number,
+                        name
+                    )
+                })?;
+                converted.insert(name, material.to_material());
+            }
+        }
+    }
+
+    let meshes = obj
+        .groups
+        .iter()
+        .map(|group| {
+            let material = match &group.material {
+                Some((name, _)) => Arc::clone(&converted[name.as_str()]),
+                None => Arc::clone(&default_material),
+            };
+            obj.build_mesh(group, material)
+        })
+        .collect();
+    Ok((meshes, library_paths))
+}
+
+/// Load the meshes of a Wavefront OBJ file using the same material for every face.
+///
+/// Material libraries referenced by the file are not read.
+pub fn load_obj_with_material<P: AsRef<Path>>(
+    path: P,
+    material: Arc<dyn Material>,
+) -> anyhow::Result<Vec<ObjMesh>> {
+    let obj = parse_obj_file(path.as_ref())?;
+    Ok(obj
+        .groups
+        .iter()
+        .map(|group| obj.build_mesh(group, Arc::clone(&material)))
+        .collect())
+}
+
+/// A run of faces sharing an object/group name and material
+struct Group {
+    name: String,
+    /// Material name and the line on which it was selected
+    material: Option<(String, usize)>,
+    faces: Vec<Face>,
+}
+
+/// The raw contents of an OBJ file
+#[derive(Default)]
+struct Obj {
+    positions: Vec<VecR3>,
+    normals: Vec<VecR3>,
+    uvs: Vec<(f64, f64)>,
+    groups: Vec<Group>,
+    /// Material library file names and the lines on which they are referenced
+    material_libraries: Vec<(String, usize)>,
+}
+
+impl Obj {
+    /// Create a mesh from a group, copying only the vertex data used by its faces.
+    fn build_mesh(&self, group: &Group, material: Arc<dyn Material>) -> ObjMesh {
+        let mut positions = Remap::default();
+        let mut normals = Remap::default();
+        let mut uvs = Remap::default();
+        let faces = group
+            .faces
+            .iter()
+            .map(|face| Face {
+                positions: positions.map3(face.positions, &self.positions),
+                normals: face.normals.map(|n| normals.map3(n, &self.normals)),
+                uvs: face.uvs.map(|uv| uvs.map3(uv, &self.uvs)),
+            })
+            .collect();
+        ObjMesh {
+            name: group.name.clone(),
+            material_name: group.material.as_ref().map(|(name, _)| name.clone()),
+            mesh: TriangleMesh::new(
+                positions.values,
+                normals.values,
+                uvs.values,
+                faces,
+                material,
+            ),
+        }
+    }
+}
+
+/// Compacts a vertex buffer by assigning new indices to the used entries.
+struct Remap<T> {
+    indices: HashMap<usize, usize>,
+    values: Vec<T>,
+}
+
+impl<T> Default for Remap<T> {
+    fn default() -> Self {
+        Self {
+            indices: HashMap::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<T: Copy> Remap<T> {
+    fn map3(&mut self, indices: [usize; 3], source: &[T]) -> [usize; 3] {
+        let mut result = [0; 3];
+        for (new_index, &index) in result.iter_mut().zip(&indices) {
+            let values = &mut self.values;
+            *new_index = *self.indices.entry(index).or_insert_with(|| {
+                values.push(source[index]);
+                values.len() - 1
+            });
+        }
+        result
+    }
+}
+
+/// Parse the geometry of an OBJ file.
+fn parse_obj_file(path: &Path) -> anyhow::Result<Obj> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read mesh '{}'", path.display()))?;
+
+    let mut obj = Obj::default();
+    let mut name = String::new();
+    let mut material: Option<(String, usize)> = None;
+    let mut faces: Vec<Face> = Vec::new();
+
+    let mut lines = text.lines().enumerate();
+    while let Some((line_index, line)) = lines.next() {
+        let line_number = line_index + 1;
+        // Join lines continued with a trailing backslash
+        let mut line = line.to_string();
+        while line.ends_with('\\') {
+            line.pop();
+            match lines.next() {
+                Some((_, next)) => line.push_str(next),
+                None => break,
+            }
+        }
+
+        let (keyword, args) = match split_statement(&line) {
+            Some(statement) => statement,
+            None => continue,
+        };
+        let result = (|| -> anyhow::Result<()> {
+            match keyword {
+                "v" => {
+                    // Optional trailing w or vertex colour values are ignored
+                    let [x, y, z] = parse_args::<f64, 3>(args.get(..3).unwrap_or(&args[..]))?;
+                    obj.positions.push(Vec3::new(x, y, z));
+                }
+                "vn" => {
+                    let [x, y, z] = parse_args::<f64, 3>(&args)?;
+                    obj.normals.push(Vec3::new(x, y, z));
+                }
+                "vt" => {
+                    let (u, v) = match args.len() {
+                        1 => (parse_args::<f64, 1>(&args)?[0], 0.0),
+                        2 | 3 => {
+                            let [u, v] = parse_args::<f64, 2>(&args[..2])?;
+                            (u, v)
+                        }
+                        n => return Err(anyhow!("expected 1 to 3 values, got {}", n)),
+                    };
+                    obj.uvs.push((u, v));
+                }
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(anyhow!("face has fewer than 3 vertices"));
+                    }
+                    let vertices = args
+                        .iter()
+                        .map(|arg| parse_face_vertex(arg, &obj))
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    let has_normals = vertices.iter().all(|v| v.normal.is_some());
+                    let has_uvs = vertices.iter().all(|v| v.uv.is_some());
+                    // Triangulate as a fan around the first vertex
+                    for i in 1..vertices.len() - 1 {
+                        let triangle = [vertices[0], vertices[i], vertices[i + 1]];
+                        faces.push(Face {
+                            positions: [
+                                triangle[0].position,
+                                triangle[1].position,
+                                triangle[2].position,
+                            ],
+                            normals: if has_normals {
+                                Some([
+                                    triangle[0].normal.unwrap(),
+                                    triangle[1].normal.unwrap(),
+                                    triangle[2].normal.unwrap(),
+                                ])
+                            } else {
+                                None
+                            },
+                            uvs: if has_uvs {
+                                Some([
+                                    triangle[0].uv.unwrap(),
+                                    triangle[1].uv.unwrap(),
+                                    triangle[2].uv.unwrap(),
+                                ])
+                            } else {
+                                None
+                            },
+                        });
+                    }
+                }
+                "o" | "g" | "usemtl" => {
+                    if !faces.is_empty() {
+                        obj.groups.push(Group {
+                            name: name.clone(),
+                            material: material.clone(),
+                            faces: std::mem::take(&mut faces),
+                        });
+                    }
+                    if keyword == "usemtl" {
+                        if args.is_empty() {
+                            return Err(anyhow!("missing material name"));
+                        }
+                        material = Some((args.join(" "), line_number));
+                    } else {
+                        name = args.join(" ");
+                    }
+                }
+                "mtllib" => {
+                    if args.is_empty() {
+                        return Err(anyhow!("missing material library name"));
+                    }
+                    for library in args.iter() {
+                        obj.material_libraries
+                            .push((library.to_string(), line_number));
+                    }
+                }
+                // Smoothing groups, lines, points, free-form geometry, etc. are ignored
+                _ => {}
+            }
+            Ok(())
+        })();
+        result.with_context(|| format!("{}:{}", path.display(), line_number))?;
+    }
+    if !faces.is_empty() {
+        obj.groups.push(Group {
+            name,
+            material,
+            faces,
+        });
+    }
+    Ok(obj)
+}
+
+/// Indices of the attributes of a single face vertex
+#[derive(Debug, Clone, Copy)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Parse a face vertex of the form `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+fn parse_face_vertex(arg: &str, obj: &Obj) -> anyhow::Result<FaceVertex> {
+    let mut parts = arg.split('/');
+    let position = match parts.next() {
+        Some(s) => resolve_index(s, obj.positions.len(), "position")?,
+        None => return Err(anyhow!("empty face vertex")),
+    };
+    let uv = match parts.next() {
+        Some(s) if !s.is_empty() => Some(resolve_index(s, obj.uvs.len(), "texture coordinate")?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(s) if !s.is_empty() => Some(resolve_index(s, obj.normals.len(), "normal")?),
+        _ => None,
+    };
+    if parts.next().is_some() {
+        return Err(anyhow!("invalid face vertex '{}'", arg));
+    }
+    Ok(FaceVertex {
+        position,
+        uv,
+        normal,
+    })
+}
+
+/// Convert a 1-based or negative (relative) OBJ index into a 0-based index.
+fn resolve_index(s: &str, len: usize, kind: &str) -> anyhow::Result<usize> {
+    let index: i64 = s
+        .parse()
+        .with_context(|| format!("invalid {} index '{}'", kind, s))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        len as i64 + index
+    };
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(anyhow!(
+            "{} index {} out of range; {} defined so far",
+            kind,
+            index,
+            len
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::temp_file;
+    use super::*;
+
+    /// Parse an OBJ file and return the faces of each group.
+    fn faces(name: &str, contents: &str) -> Vec<Vec<Face>> {
+        let obj = parse_obj_file(&temp_file(name, contents)).unwrap();
+        obj.groups.into_iter().map(|group| group.faces).collect()
+    }
+
+    /// The error message from loading an OBJ file, and the expected `file:line` prefix.
+    fn error(name: &str, contents: &str, line: usize) -> (String, String) {
+        let path = temp_file(name, contents);
+        let error = load_obj(&path).err().expect("file should be invalid");
+        (
+            format!("{:#}", error),
+            format!("{}:{}: ", path.display(), line),
+        )
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn face_vertex_forms() {
+        let contents = format!(
+            "{}vt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\n\
+             f 1/1/1 2/2/1 3/3/1\nf 1/1 3/3 4/2\nf 1//1 3//1 4//1\n",
+            SQUARE
+        );
+        let groups = faces("forms.obj", &contents);
+        let faces = &groups[0];
+        assert_eq!(faces.len(), 3);
+        assert_eq!(faces[0].positions, [0, 1, 2]);
+        assert_eq!(faces[0].uvs, Some([0, 1, 2]));
+        assert_eq!(faces[0].normals, Some([0, 0, 0]));
+        assert_eq!(faces[1].uvs, Some([0, 2, 1]));
+        assert_eq!(faces[1].normals, None);
+        assert_eq!(faces[2].uvs, None);
+        assert_eq!(faces[2].normals, Some([0, 0, 0]));
+    }
+
+    #[test]
+    fn relative_indices() {
+        // Negative indices count back from the most recent vertex
+        let contents = format!("{}f -4 -2 -1\nv 2 2 0\nf -1 -2 -3\n", SQUARE);
+        let groups = faces("relative.obj", &contents);
+        assert_eq!(groups[0][0].positions, [0, 2, 3]);
+        assert_eq!(groups[0][1].positions, [4, 3, 2]);
+    }
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let contents = format!("{}v 0.5 2 0\nf 1 2 3 4\nf 1 2 3 5 4\n", SQUARE);
+        let groups = faces("polygons.obj", &contents);
+        let positions: Vec<_> = groups[0].iter().map(|face| face.positions).collect();
+        assert_eq!(
+            positions,
+            [[0, 1, 2], [0, 2, 3], [0, 1, 2], [0, 2, 4], [0, 4, 3]]
+        );
+    }
+
+    #[test]
+    fn errors_name_file_and_line() {
+        let (message, location) = error("range.obj", &format!("{}f 1 2 5\n", SQUARE), 5);
+        assert_eq!(
+            message,
+            format!(
+                "{}position index 5 out of range; 4 defined so far",
+                location
+            )
+        );
+        let (message, location) = error("zero.obj", &format!("{}f 0 1 2\n", SQUARE), 5);
+        assert_eq!(
+            message,
+            format!(
+                "{}position index 0 out of range; 4 defined so far",
+                location
+            )
+        );
+        let (message, location) = error("normal.obj", &format!("{}f 1//1 2//1 3//1\n", SQUARE), 5);
+        assert_eq!(
+            message,
+            format!("{}normal index 1 out of range; 0 defined so far", location)
+        );
+        let (message, location) = error("number.obj", "v 0 0 0\nv 1 x 0\n", 2);
+        assert!(
+            message.starts_with(&format!("{}invalid number 'x'", location)),
+            "{}",
+            message
+        );
+        let (message, location) = error("index.obj", &format!("{}f 1 2 a\n", SQUARE), 5);
+        assert!(
+            message.starts_with(&format!("{}invalid position index 'a'", location)),
+            "{}",
+            message
+        );
+        let (message, location) = error("library.obj", "# materials\nmtllib missing.mtl\n", 2);
+        assert!(
+            message.starts_with(&format!("{}failed to read material library", location)),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn groups_and_materials() {
+        let library = temp_file("groups.mtl", "newmtl red\nKd 1 0 0\n");
+        let contents = format!(
+            "mtllib {}\n{}o first\nf 1 2 3\nusemtl red\nf 1 3 4\n",
+            library.file_name().unwrap().to_str().unwrap(),
+            SQUARE
+        );
+        let path = temp_file("groups.obj", &contents);
+        let (meshes, libraries) = load_obj_with_libraries(&path).unwrap();
+        assert_eq!(libraries, [library]);
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].name, "first");
+        assert_eq!(meshes[0].material_name, None);
+        assert_eq!(meshes[1].name, "first");
+        assert_eq!(meshes[1].material_name.as_deref(), Some("red"));
+    }
+}
@@ -1,19 +1,33 @@
-use anyhow;
+use anyhow::{anyhow, Context};
 use clap::Clap;
-use image::RgbImage;
-use indicatif::{ProgressBar, ProgressIterator};
-use rand;
+use indicatif::ProgressBar;
+use rand::{Rng, SeedableRng};
+use raytracer::checkpoint::{hash_bytes, Checkpoint};
 use raytracer::colour;
+use raytracer::environments::{Black, Constant, EnvironmentMap, Gradient};
+use raytracer::film::Filter;
+use raytracer::loaders::is_float_image;
 use raytracer::materials::{Lambertian, Metal, Transparent};
 use raytracer::objects::Sphere;
-use raytracer::threadpool;
-use raytracer::{Camera, Colour, Ratio, RayR3, Surface, Vec3};
-use std::f64;
+use raytracer::render::{AdaptiveSampling, CancelToken, Integrator, MisHeuristic, RenderError};
+use raytracer::samplers::SamplerKind;
+use raytracer::sampling::SampleRng;
+use raytracer::scene::CameraSettings;
+use raytracer::tiles::TileOrder;
+use raytracer::tone_mapping::{ToneMapping, ToneOperator};
+use raytracer::{Bvh, Colour, Environment, Ratio, RenderSettings, Renderer, Scene, Surface, Vec3};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Clap)]
 #[clap(version = "0.1.0", author = "Eric Langlois")]
 pub struct Opts {
+    /// Output image. The format is chosen by extension: .exr, .hdr and .pfm store linear
+    /// floating-point values; other formats such as .png and .jpg store 8-bit colour.
     #[clap(short, long, default_value = "image.png")]
     pub output: String,
 
@@ -26,124 +40,380 @@ pub struct Opts {
     #[clap(short, long, default_value = "400")]
     pub width: u32,
 
-    #[clap(short, long, default_value = "100")]
+    /// Samples per pixel; the maximum number of samples per pixel with adaptive sampling.
+    #[clap(short, long, visible_alias = "max-spp", default_value = "100")]
     pub samples_per_pixel: u32,
 
+    /// Minimum samples per pixel with adaptive sampling.
+    #[clap(long, default_value = "16")]
+    pub min_spp: u32,
+
+    /// Enable adaptive sampling: pixels stop taking samples once the standard error
+    /// of their mean luminance relative to the mean is at most this value (such as 0.01).
+    #[clap(long)]
+    pub noise_threshold: Option<f64>,
+
+    /// Render in passes of one sample per pixel so that a partial render is a complete image.
+    #[clap(long)]
+    pub progressive: bool,
+
+    /// Save the image so far to the output file every this many seconds.
+    /// Implies --progressive.
+    #[clap(long)]
+    pub preview_interval: Option<f64>,
+
+    /// Stop rendering after this many seconds and save the image so far.
+    /// Implies --progressive.
+    #[clap(long)]
+    pub time_limit: Option<f64>,
+
+    /// Periodically save the state of the render to this file so that it can be resumed.
+    /// Implies --progressive.
+    #[clap(long)]
+    pub checkpoint: Option<String>,
+
+    /// Seconds between checkpoints.
+    #[clap(long, default_value = "300")]
+    pub checkpoint_interval: f64,
+
+    /// Continue the render saved in the --checkpoint file.
+    /// The scene and settings must be the same as those of the saved render.
+    #[clap(long, requires = "checkpoint")]
+    pub resume: bool,
+
+    /// Also save an image of the number of samples taken in each pixel,
+    /// scaled linearly so that the most sampled pixel is white.
+    /// 8-bit formats store the values without sRGB encoding.
+    #[clap(long)]
+    pub spp_image: Option<String>,
+
     #[clap(short('d'), long, default_value = "50")]
     pub max_depth: u32,
-}
 
-fn main() -> Result<(), anyhow::Error> {
-    let opts: Opts = Opts::parse();
-    let image_width = opts.width;
-    let image_height = opts.aspect_ratio.a_to_b(image_width);
-    println!("Image dimensions: {} x {}", image_width, image_height);
+    /// Scene description file. Renders a random scene of spheres if omitted.
+    #[clap(long)]
+    pub scene: Option<String>,
 
-    // Image
-    let mut image = RgbImage::new(image_width, image_height);
+    /// Override the scene background.
+    /// One of "black", "gradient", "gradient:R,G,B:R,G,B" (bottom:top), a colour "R,G,B",
+    /// or the path of an equirectangular .hdr or .exr environment map, optionally followed by
+    /// a rotation in degrees and an intensity ("PATH[:ROTATION[:INTENSITY]]").
+    #[clap(short, long)]
+    pub background: Option<BackgroundOpt>,
 
-    // World
-    let world = random_scene();
+    /// Light transport algorithm. One of "path" (unidirectional path tracing),
+    /// "nee" (path tracing with explicit light sampling), or
+    /// "mis" (explicit light sampling combined with material sampling).
+    #[clap(short, long, default_value = "mis")]
+    pub integrator: Integrator,
 
-    // Camera
-    let lookfrom = Vec3::new(13.0, 2.0, 3.0);
-    let lookat = Vec3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    // let distance_to_focus = (lookfrom - lookat).norm();
-    let distance_to_focus = 10.0;
-    let aperture = 0.1;
-    let camera = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        20.0,
-        opts.aspect_ratio.into(),
-        aperture,
-        distance_to_focus,
-    );
+    /// Multiple importance sampling heuristic used by the "mis" integrator.
+    /// One of "balance" or "power".
+    #[clap(long, default_value = "power")]
+    pub mis_heuristic: MisHeuristic,
 
-    // Render
-    println!("Rendering...");
-    let render_state = Arc::new(RenderState {
-        camera,
-        surface: world,
-        image_width,
-        image_height,
-        max_depth: opts.max_depth,
-        samples_per_pixel: opts.samples_per_pixel,
-    });
-
-    let bar = ProgressBar::new((image_height * image_width) as u64);
-    bar.set_draw_delta(5000 / opts.samples_per_pixel as u64);
-    for ((x, y), colour) in threadpool::map(
-        image.enumerate_pixels().map(|(x, y, _pixel)| (x, y)),
-        move |(x, y)| render_pixel(&render_state, x, y),
-        opts.num_workers,
-    )
-    .progress_with(bar)
-    {
-        image[(x, y)] = colour.into();
+    /// Random seed. Renders with the same seed and options are identical.
+    #[clap(long, default_value = "0")]
+    pub seed: u64,
+
+    /// Exposure adjustment in stops for 8-bit outputs.
+    #[clap(long, default_value = "0", allow_hyphen_values = true)]
+    pub exposure: f64,
+
+    /// Tone mapping curve for 8-bit outputs. One of "clamp", "reinhard",
+    /// "reinhard-extended[:WHITE]", "aces" or "agx".
+    #[clap(long, default_value = "clamp")]
+    pub tone_map: ToneOperator,
+
+    /// Pixel reconstruction filter, optionally with a radius in pixels ("NAME:RADIUS").
+    /// One of "box", "tent", "gaussian", "mitchell" or "lanczos".
+    #[clap(long, default_value = "box")]
+    pub filter: Filter,
+
+    /// Width and height in pixels of the tiles of the image handed out to the workers.
+    #[clap(long, default_value = "32")]
+    pub tile_size: u32,
+
+    /// Order in which tiles are rendered. One of "scanline", "spiral" or "hilbert".
+    #[clap(long, default_value = "scanline")]
+    pub tile_order: TileOrder,
+
+    /// Sample generator. One of "independent", "stratified", "halton" or "sobol".
+    #[clap(long, default_value = "sobol")]
+    pub sampler: SamplerKind,
+}
+
+/// A background given on the command line
+#[derive(Debug)]
+pub enum BackgroundOpt {
+    Black,
+    Gradient(Option<(Colour, Colour)>),
+    Constant(Colour),
+    Map {
+        path: String,
+        /// Rotation about the vertical axis in degrees
+        rotation: f64,
+        intensity: f64,
+    },
+}
+
+impl BackgroundOpt {
+    fn build(&self) -> Result<Box<dyn Environment>, anyhow::Error> {
+        Ok(match *self {
+            BackgroundOpt::Black => Box::new(Black),
+            BackgroundOpt::Gradient(None) => Box::new(Gradient::default()),
+            BackgroundOpt::Gradient(Some((bottom, top))) => Box::new(Gradient::new(bottom, top)),
+            BackgroundOpt::Constant(colour) => Box::new(Constant::new(colour)),
+            BackgroundOpt::Map {
+                ref path,
+                rotation,
+                intensity,
+            } => Box::new(
+                EnvironmentMap::load(path)?
+                    .with_rotation(rotation)
+                    .with_intensity(intensity),
+            ),
+        })
     }
+}
 
-    println!("Saving image to '{}'", opts.output);
-    image.save(opts.output)?;
-    Ok(())
+impl FromStr for BackgroundOpt {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        match parts.next() {
+            Some("black") => Ok(BackgroundOpt::Black),
+            Some("gradient") => match (parts.next(), parts.next()) {
+                (None, None) => Ok(BackgroundOpt::Gradient(None)),
+                (Some(bottom), Some(top)) => Ok(BackgroundOpt::Gradient(Some((
+                    parse_colour(bottom)?,
+                    parse_colour(top)?,
+                )))),
+                _ => Err(anyhow!(
+                    "expected a gradient of the form gradient:R,G,B:R,G,B"
+                )),
+            },
+            _ => match parse_map(s)? {
+                Some(map) => Ok(map),
+                None => Ok(BackgroundOpt::Constant(parse_colour(s)?)),
+            },
+        }
+    }
 }
 
-struct RenderState<T> {
-    camera: Camera,
-    surface: T,
-    image_width: u32,
-    image_height: u32,
-    max_depth: u32,
-    samples_per_pixel: u32,
+/// Parse an environment map of the form "PATH[:ROTATION[:INTENSITY]]"
+///
+/// Returns None if the path is not of an .hdr or .exr image.
+fn parse_map(s: &str) -> Result<Option<BackgroundOpt>, anyhow::Error> {
+    let mut path = s;
+    let mut values = Vec::new();
+    // Take parameters from the end until the rest names an image
+    while !is_float_image(path) {
+        match path.rsplit_once(':') {
+            Some((rest, value)) if values.len() < 2 => {
+                values.insert(0, value);
+                path = rest;
+            }
+            _ => return Ok(None),
+        }
+    }
+    let parse = |index: usize, name: &str, default: f64| match values.get(index) {
+        Some(value) => value
+            .parse::<f64>()
+            .with_context(|| format!("invalid environment map {} '{}'", name, value)),
+        None => Ok(default),
+    };
+    Ok(Some(BackgroundOpt::Map {
+        path: path.into(),
+        rotation: parse(0, "rotation", 0.0)?,
+        intensity: parse(1, "intensity", 1.0)?,
+    }))
 }
 
-fn render_pixel<T: Surface>(state: &RenderState<T>, x: u32, y: u32) -> Colour {
-    let mut colour = Colour::new(0.0, 0.0, 0.0);
-    for _ in 0..state.samples_per_pixel {
-        // (u, v) measure from bottom left corner
-        let u = (x as f64 + rand::random::<f64>()) / ((state.image_width - 1) as f64);
-        let v = ((state.image_height - 1 - y) as f64 + rand::random::<f64>())
-            / ((state.image_height - 1) as f64);
-        let ray = state.camera.get_ray(u, v);
-        colour += ray_colour(&ray, &state.surface, state.max_depth);
+/// A hash identifying the scene described by the command line options
+///
+/// Includes the contents of the files referenced by the scene, such as meshes,
+/// and of the background map given on the command line.
+///
+/// # Arguments
+/// * `opts` - The command line options.
+/// * `files` - Files read while loading the scene, other than the scene file itself.
+fn scene_hash(opts: &Opts, files: &[PathBuf]) -> Result<u64, anyhow::Error> {
+    let mut description = match &opts.scene {
+        Some(path) => fs::read(path).with_context(|| format!("failed to read scene '{}'", path))?,
+        None => b"random scene".to_vec(),
+    };
+    description.extend(format!("{:?} {:?}", opts.aspect_ratio, opts.background).bytes());
+    let background_map = match &opts.background {
+        Some(BackgroundOpt::Map { path, .. }) => Some(Path::new(path)),
+        _ => None,
+    };
+    for path in files.iter().map(PathBuf::as_path).chain(background_map) {
+        let contents =
+            fs::read(path).with_context(|| format!("failed to read '{}'", path.display()))?;
+        description.extend(&hash_bytes(&contents).to_le_bytes());
     }
+    Ok(hash_bytes(&description))
+}
 
-    colour /= state.samples_per_pixel as f64;
-    // Gamma-correct for gamma=2
-    Colour {
-        x: colour.x.sqrt(),
-        y: colour.y.sqrt(),
-        z: colour.z.sqrt(),
+/// Convert a number of seconds given on the command line to a duration
+fn seconds(value: f64) -> Result<Duration, anyhow::Error> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(Duration::from_secs_f64(value))
+    } else {
+        Err(anyhow!(
+            "expected a non-negative number of seconds; got {}",
+            value
+        ))
     }
 }
 
-fn ray_colour<T: Surface>(ray: &RayR3, surface: &T, depth: u32) -> Colour {
-    // Exceeded ray bounce limit; no more light is gathered
-    if depth == 0 {
-        return Colour::new(0.0, 0.0, 0.0);
+/// Parse a colour of the form "R,G,B"
+fn parse_colour(s: &str) -> Result<Colour, anyhow::Error> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("invalid colour '{}'", s))?;
+    match values[..] {
+        [r, g, b] => Ok(Colour::new(r, g, b)),
+        _ => Err(anyhow!("expected a colour of the form R,G,B; got '{}'", s)),
     }
+}
 
-    // 0.001 is to prevent collisions with the object the ray is leaving; the "acne" problem.
-    if let Some(intersection) = surface.intersect(ray, 0.001, f64::INFINITY) {
-        if let Some((colour, scatter)) = intersection.scatter(ray) {
-            return colour.elementwise_mul(ray_colour(&scatter, surface, depth - 1));
-        }
-        return Colour::new(0.0, 0.0, 0.0);
+fn main() -> Result<(), anyhow::Error> {
+    let opts: Opts = Opts::parse();
+    let image_width = opts.width;
+    let image_height = opts.aspect_ratio.a_to_b(image_width);
+    println!("Image dimensions: {} x {}", image_width, image_height);
+
+    // World
+    let mut scene = match &opts.scene {
+        Some(path) => Scene::load(path)?,
+        None => random_scene(opts.seed),
+    };
+    if let Some(background) = &opts.background {
+        scene.background = background.build()?;
+    }
+
+    // Camera
+    let camera = scene.camera.build(opts.aspect_ratio.into());
+
+    // Render
+    println!("Rendering...");
+    let settings = RenderSettings {
+        width: image_width,
+        height: image_height,
+        samples_per_pixel: opts.samples_per_pixel,
+        max_depth: opts.max_depth,
+        num_workers: opts.num_workers,
+        integrator: opts.integrator,
+        mis_heuristic: opts.mis_heuristic,
+        seed: opts.seed,
+        sampler: opts.sampler,
+        filter: opts.filter,
+        tile_size: opts.tile_size,
+        tile_order: opts.tile_order,
+        adaptive: opts
+            .noise_threshold
+            .map(|noise_threshold| AdaptiveSampling {
+                min_samples: opts.min_spp,
+                noise_threshold,
+            }),
+    };
+    let renderer = Renderer::new(camera, scene.world, scene.background, settings);
+
+    let tone_mapping = ToneMapping::new(opts.exposure, opts.tone_map);
+    let cancel = CancelToken::new();
+    if let Some(time_limit) = opts.time_limit {
+        let time_limit = seconds(time_limit)?;
+        let cancel = cancel.clone();
+        thread::spawn(move || {
+            thread::sleep(time_limit);
+            cancel.cancel();
+        });
     }
+    let progressive = opts.progressive
+        || opts.preview_interval.is_some()
+        || opts.time_limit.is_some()
+        || opts.checkpoint.is_some();
+    let framebuffer = if progressive {
+        let preview_interval = opts.preview_interval.map(seconds).transpose()?;
+        let checkpoint_interval = seconds(opts.checkpoint_interval)?;
+        let scene_hash = scene_hash(&opts, &scene.files)?;
+        let mut render = if opts.resume {
+            let path = opts.checkpoint.as_ref().unwrap();
+            println!("Resuming from '{}'", path);
+            let checkpoint = Checkpoint::load(path)?;
+            renderer
+                .resume_progressive(checkpoint, scene_hash)
+                .with_context(|| format!("cannot resume from '{}'", path))?
+        } else {
+            renderer.start_progressive()
+        };
 
-    // A simple gradient
-    let unit_direction = ray.direction.as_unit();
-    let t = (unit_direction.y + 1.0) / 2.0;
-    Colour::new(1.0, 1.0, 1.0) * (1.0 - t) + Colour::new(0.5, 0.7, 1.0) * t
+        let bar =
+            ProgressBar::new((image_height * image_width) as u64 * opts.samples_per_pixel as u64);
+        let mut last_preview = Instant::now();
+        let mut last_checkpoint = Instant::now();
+        while !render.is_finished() {
+            let result = render.render_pass(
+                |progress, film| {
+                    bar.set_position(progress.completed);
+                    match preview_interval {
+                        Some(interval) if last_preview.elapsed() >= interval => {
+                            let preview = film.to_framebuffer();
+                            if let Err(err) = preview.save(&opts.output, &tone_mapping) {
+                                bar.println(format!("Failed to save preview: {:#}", err));
+                            }
+                            last_preview = Instant::now();
+                        }
+                        _ => {}
+                    }
+                },
+                &cancel,
+            );
+            if let Some(path) = &opts.checkpoint {
+                if result.is_err()
+                    || render.is_finished()
+                    || last_checkpoint.elapsed() >= checkpoint_interval
+                {
+                    render.checkpoint(scene_hash).save(path)?;
+                    last_checkpoint = Instant::now();
+                }
+            }
+            match result {
+                Ok(()) => {}
+                Err(RenderError::Cancelled) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        bar.finish();
+        render.to_framebuffer()
+    } else {
+        let bar = ProgressBar::new((image_height * image_width) as u64);
+        let framebuffer =
+            renderer.render_with(|progress| bar.set_position(progress.completed), &cancel)?;
+        bar.finish();
+        framebuffer
+    };
+
+    println!("Saving image to '{}'", opts.output);
+    framebuffer.save(&opts.output, &tone_mapping)?;
+    if let Some(path) = &opts.spp_image {
+        println!("Saving sample counts to '{}'", path);
+        framebuffer.sample_count_image().save_linear(path)?;
+    }
+    Ok(())
 }
 
-fn random_scene() -> Vec<Box<dyn Surface>> {
-    let mut world: Vec<Box<dyn Surface>> = Vec::new();
+fn random_scene(seed: u64) -> Scene {
+    let mut rng = SampleRng::seed_from_u64(seed);
+    let mut world: Vec<Arc<dyn Surface>> = Vec::new();
 
     let ground_material = Lambertian::new(Colour::new(0.5, 0.5, 0.5));
-    world.push(Box::new(Sphere::new(
+    world.push(Arc::new(Sphere::new(
         Vec3::new(0.0, -1000.0, 0.0),
         1000.0,
         ground_material,
@@ -151,11 +421,11 @@ fn random_scene() -> Vec<Box<dyn Surface>> {
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_material: f64 = rand::random();
+            let choose_material: f64 = rng.gen();
             let center = Vec3::new(
-                (a as f64) + 0.9 * rand::random::<f64>(),
+                (a as f64) + 0.9 * rng.gen::<f64>(),
                 0.2,
-                (b as f64) + 0.9 * rand::random::<f64>(),
+                (b as f64) + 0.9 * rng.gen::<f64>(),
             );
             let radius = 0.2;
 
@@ -165,43 +435,55 @@ fn random_scene() -> Vec<Box<dyn Surface>> {
 
             if choose_material < 0.8 {
                 // Diffuse
-                let colour = colour::random().elementwise_mul(colour::random());
+                let colour = colour::random(&mut rng).elementwise_mul(colour::random(&mut rng));
                 let material = Lambertian::new(colour);
-                world.push(Box::new(Sphere::new(center, radius, material)));
+                world.push(Arc::new(Sphere::new(center, radius, material)));
             } else if choose_material < 0.95 {
                 // Metal
-                let colour = colour::random_range(0.5, 1.0);
-                let fuzz = rand::random::<f64>() / 2.0;
+                let colour = colour::random_range(&mut rng, 0.5, 1.0);
+                let fuzz = rng.gen::<f64>() / 2.0;
                 let material = Metal::new(colour, fuzz);
-                world.push(Box::new(Sphere::new(center, radius, material)));
+                world.push(Arc::new(Sphere::new(center, radius, material)));
             } else {
                 // Glass
                 let material = Transparent::new(1.5);
-                world.push(Box::new(Sphere::new(center, radius, material)));
+                world.push(Arc::new(Sphere::new(center, radius, material)));
             }
         }
     }
 
     let material = Transparent::new(1.5);
-    world.push(Box::new(Sphere::new(
+    world.push(Arc::new(Sphere::new(
         Vec3::new(0.0, 1.0, 0.0),
         1.0,
         material,
     )));
 
     let material = Lambertian::new(Colour::new(0.4, 0.2, 0.1));
-    world.push(Box::new(Sphere::new(
+    world.push(Arc::new(Sphere::new(
         Vec3::new(-4.0, 1.0, 0.0),
         1.0,
         material,
     )));
 
     let material = Metal::new(Colour::new(0.7, 0.6, 0.5), 0.0);
-    world.push(Box::new(Sphere::new(
+    world.push(Arc::new(Sphere::new(
         Vec3::new(4.0, 1.0, 0.0),
         1.0,
         material,
     )));
 
-    world
+    Scene {
+        camera: CameraSettings {
+            lookfrom: [13.0, 2.0, 3.0],
+            lookat: [0.0, 0.0, 0.0],
+            vup: [0.0, 1.0, 0.0],
+            vfov: 20.0,
+            aperture: 0.1,
+            focus_distance: Some(10.0),
+        },
+        background: Box::new(Gradient::default()),
+        world: Bvh::new(world),
+        files: Vec::new(),
+    }
 }
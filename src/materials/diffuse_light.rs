@@ -0,0 +1,45 @@
+use crate::colour::Colour;
+use crate::materials::Material;
+use crate::ray::RayR3;
+use crate::samplers::Sampler;
+use crate::vec3::VecR3;
+
+/// A light source that emits the same colour in every direction
+///
+/// Light is only emitted from the front face of the surface.
+/// Incident light is absorbed.
+#[derive(Debug, Clone)]
+pub struct DiffuseLight {
+    pub colour: Colour,
+}
+
+impl DiffuseLight {
+    pub fn new(colour: Colour) -> Self {
+        Self { colour }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray: &RayR3,
+        _point: &VecR3,
+        _normal: &VecR3,
+        _front_face: bool,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<(Colour, RayR3)> {
+        None
+    }
+
+    fn emitted(&self, _point: &VecR3, front_face: bool) -> Colour {
+        if front_face {
+            self.colour
+        } else {
+            Colour::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
@@ -1,8 +1,10 @@
 use crate::colour::Colour;
 use crate::materials::Material;
 use crate::ray::RayR3;
+use crate::samplers::Sampler;
+use crate::sampling::uniform_sphere;
 use crate::vec3::VecR3;
-use rand;
+use std::f64::consts::PI;
 
 /// A Lambertian (matte) material
 #[derive(Debug, Clone)]
@@ -23,8 +25,9 @@ impl Material for Lambertian {
         point: &VecR3,
         normal: &VecR3,
         _front_face: bool,
+        sampler: &mut dyn Sampler,
     ) -> Option<(Colour, RayR3)> {
-        let mut scatter_direction = *normal + rand::random();
+        let mut scatter_direction = *normal + uniform_sphere(sampler.get_2d());
         if scatter_direction.near_zero() {
             scatter_direction = *normal;
         }
@@ -32,4 +35,17 @@ impl Material for Lambertian {
         let scattered = RayR3::new(*point, scatter_direction);
         Some((self.colour, scattered))
     }
+
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, wi: &VecR3, _wo: &VecR3, normal: &VecR3, _front_face: bool) -> Colour {
+        self.colour * (wi.dot(*normal).max(0.0) / PI)
+    }
+
+    /// Scattered directions are cosine-weighted.
+    fn pdf(&self, wi: &VecR3, _wo: &VecR3, normal: &VecR3, _front_face: bool) -> f64 {
+        wi.dot(*normal).max(0.0) / PI
+    }
 }
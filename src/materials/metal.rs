@@ -1,8 +1,10 @@
 use crate::colour::Colour;
 use crate::materials::Material;
 use crate::ray::RayR3;
+use crate::samplers::Sampler;
+use crate::sampling::uniform_sphere;
 use crate::vec3::VecR3;
-use rand;
+use std::f64::consts::PI;
 
 /// A reflective metal surface
 #[derive(Debug, Clone)]
@@ -18,6 +20,27 @@ impl Metal {
             fuzz: fuzz.min(1.0),
         }
     }
+
+    /// Probability density of `scatter` producing the unit direction `wi`
+    /// given the unit mirror reflection direction.
+    ///
+    /// Scattered directions point from the origin to a uniformly random point on the sphere
+    /// of radius `fuzz` centred on the mirror reflection direction.
+    fn fuzz_pdf(&self, wi: &VecR3, reflection: &VecR3) -> f64 {
+        // Points t * wi on the fuzz sphere satisfy t^2 - 2 t cos + 1 - fuzz^2 = 0
+        let cos = wi.dot(*reflection);
+        let quarter_discriminant = cos * cos - 1.0 + self.fuzz * self.fuzz;
+        if quarter_discriminant <= 0.0 {
+            return 0.0;
+        }
+        let root = quarter_discriminant.sqrt();
+        // Change of variables from area on the fuzz sphere to solid angle, for each point
+        [cos - root, cos + root]
+            .iter()
+            .filter(|&&t| t > 0.0)
+            .map(|t| t * t / (4.0 * PI * self.fuzz * root))
+            .sum()
+    }
 }
 
 impl Material for Metal {
@@ -27,13 +50,34 @@ impl Material for Metal {
         point: &VecR3,
         normal: &VecR3,
         _front_face: bool,
+        sampler: &mut dyn Sampler,
     ) -> Option<(Colour, RayR3)> {
         let reflection = ray.direction.as_unit().reflect(*normal);
-        let scattered = RayR3::new(*point, reflection + rand::random::<VecR3>() * self.fuzz);
+        let scattered = RayR3::new(
+            *point,
+            reflection + uniform_sphere(sampler.get_2d()) * self.fuzz,
+        );
         if scattered.direction.dot(*normal) < 0.0 {
             None
         } else {
             Some((self.colour, scattered))
         }
     }
+
+    fn is_delta(&self) -> bool {
+        self.fuzz == 0.0
+    }
+
+    fn eval(&self, wi: &VecR3, wo: &VecR3, normal: &VecR3, front_face: bool) -> Colour {
+        // The attenuation of every scattered ray is the colour,
+        // so the scattering function times cosine equals colour times the density.
+        self.colour * self.pdf(wi, wo, normal, front_face)
+    }
+
+    fn pdf(&self, wi: &VecR3, wo: &VecR3, normal: &VecR3, _front_face: bool) -> f64 {
+        if self.is_delta() || wi.dot(*normal) < 0.0 {
+            return 0.0;
+        }
+        self.fuzz_pdf(wi, &(-*wo).reflect(*normal))
+    }
 }
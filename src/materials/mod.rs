@@ -1,14 +1,18 @@
+pub mod diffuse_light;
 pub mod lambertian;
 pub mod metal;
 pub mod transparent;
 
+pub use diffuse_light::DiffuseLight;
 pub use lambertian::Lambertian;
 pub use metal::Metal;
 pub use transparent::Transparent;
 
 use crate::colour::Colour;
 use crate::ray::RayR3;
+use crate::samplers::Sampler;
 use crate::vec3::VecR3;
+use std::sync::Arc;
 
 /// A surface material
 pub trait Material: Sync + Send {
@@ -20,6 +24,7 @@ pub trait Material: Sync + Send {
     /// * `point` - The intersection point
     /// * `normal` - The intersection unit normal; `ray.direction.dot(normal) < 0`
     /// * `front_face` - Whether the intersection is on the front side of the surface or back.
+    /// * `sampler` - Source of sample values for choosing the scattered direction.
     ///
     /// # Returns
     /// If a scatter occurs, returns an attenuation colour and the scattered ray.
@@ -30,5 +35,130 @@ pub trait Material: Sync + Send {
         point: &VecR3,
         normal: &VecR3,
         front_face: bool,
+        sampler: &mut dyn Sampler,
     ) -> Option<(Colour, RayR3)>;
+
+    /// Light emitted by the surface at an intersection point
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The intersection point
+    /// * `front_face` - Whether the intersection is on the front side of the surface or back.
+    ///
+    /// # Returns
+    /// The emitted radiance. Black unless the material is a light source.
+    fn emitted(&self, _point: &VecR3, _front_face: bool) -> Colour {
+        Colour::new(0.0, 0.0, 0.0)
+    }
+
+    /// Whether the material is a light source; `emitted` may be non-zero.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Whether the material only scatters into a discrete set of directions.
+    ///
+    /// True for perfect mirrors and glass, for which `eval` is zero almost everywhere.
+    /// Light sources cannot be sampled explicitly from such materials.
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    /// Evaluate the scattering function times the cosine of the incident angle.
+    ///
+    /// # Arguments
+    ///
+    /// * `wi` - Unit direction from the intersection point towards the incident light.
+    /// * `wo` - Unit direction from the intersection point towards the viewer.
+    /// * `normal` - The intersection unit normal; `wo.dot(normal) > 0`
+    /// * `front_face` - Whether the intersection is on the front side of the surface or back.
+    ///
+    /// # Returns
+    /// The fraction of radiance arriving from `wi` that is scattered towards `wo`,
+    /// per unit solid angle. Zero for delta materials.
+    fn eval(&self, _wi: &VecR3, _wo: &VecR3, _normal: &VecR3, _front_face: bool) -> Colour {
+        Colour::new(0.0, 0.0, 0.0)
+    }
+
+    /// Probability density with respect to solid angle of `scatter` producing a direction.
+    ///
+    /// # Arguments
+    ///
+    /// * `wi` - Unit direction of the scattered ray.
+    /// * `wo` - Unit direction from the intersection point towards the viewer.
+    /// * `normal` - The intersection unit normal; `wo.dot(normal) > 0`
+    /// * `front_face` - Whether the intersection is on the front side of the surface or back.
+    ///
+    /// # Returns
+    /// The density, which is zero for delta materials.
+    fn pdf(&self, _wi: &VecR3, _wo: &VecR3, _normal: &VecR3, _front_face: bool) -> f64 {
+        0.0
+    }
+}
+
+impl<M: Material + ?Sized> Material for Box<M> {
+    fn scatter(
+        &self,
+        ray: &RayR3,
+        point: &VecR3,
+        normal: &VecR3,
+        front_face: bool,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Colour, RayR3)> {
+        (**self).scatter(ray, point, normal, front_face, sampler)
+    }
+
+    fn emitted(&self, point: &VecR3, front_face: bool) -> Colour {
+        (**self).emitted(point, front_face)
+    }
+
+    fn is_emissive(&self) -> bool {
+        (**self).is_emissive()
+    }
+
+    fn is_delta(&self) -> bool {
+        (**self).is_delta()
+    }
+
+    fn eval(&self, wi: &VecR3, wo: &VecR3, normal: &VecR3, front_face: bool) -> Colour {
+        (**self).eval(wi, wo, normal, front_face)
+    }
+
+    fn pdf(&self, wi: &VecR3, wo: &VecR3, normal: &VecR3, front_face: bool) -> f64 {
+        (**self).pdf(wi, wo, normal, front_face)
+    }
+}
+
+/// Allows materials to be shared between several surfaces.
+impl<M: Material + ?Sized> Material for Arc<M> {
+    fn scatter(
+        &self,
+        ray: &RayR3,
+        point: &VecR3,
+        normal: &VecR3,
+        front_face: bool,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Colour, RayR3)> {
+        (**self).scatter(ray, point, normal, front_face, sampler)
+    }
+
+    fn emitted(&self, point: &VecR3, front_face: bool) -> Colour {
+        (**self).emitted(point, front_face)
+    }
+
+    fn is_emissive(&self) -> bool {
+        (**self).is_emissive()
+    }
+
+    fn is_delta(&self) -> bool {
+        (**self).is_delta()
+    }
+
+    fn eval(&self, wi: &VecR3, wo: &VecR3, normal: &VecR3, front_face: bool) -> Colour {
+        (**self).eval(wi, wo, normal, front_face)
+    }
+
+    fn pdf(&self, wi: &VecR3, wo: &VecR3, normal: &VecR3, front_face: bool) -> f64 {
+        (**self).pdf(wi, wo, normal, front_face)
+    }
 }
@@ -1,6 +1,7 @@
 use crate::colour::Colour;
 use crate::materials::Material;
 use crate::ray::RayR3;
+use crate::samplers::Sampler;
 use crate::vec3::VecR3;
 
 /// A fully transparent surface that always refracts when possible
@@ -22,6 +23,7 @@ impl Material for Transparent {
         point: &VecR3,
         normal: &VecR3,
         front_face: bool,
+        sampler: &mut dyn Sampler,
     ) -> Option<(Colour, RayR3)> {
         let refraction_ratio = if front_face {
             self.refractive_index.recip()
@@ -31,7 +33,7 @@ impl Material for Transparent {
         let unit_direction = ray.direction.as_unit();
         let cos_theta = (-unit_direction.dot(*normal)).min(1.0);
         let new_direction = match unit_direction.refract(*normal, refraction_ratio) {
-            Some(refraction) if reflectance(cos_theta, refraction_ratio) < rand::random() => {
+            Some(refraction) if reflectance(cos_theta, refraction_ratio) < sampler.get_1d() => {
                 refraction
             }
             _ => unit_direction.reflect(*normal),
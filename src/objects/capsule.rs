@@ -0,0 +1,187 @@
+use crate::aabb::Aabb;
+use crate::materials::Material;
+use crate::objects::frame::{sweep_angle, Frame, LocalHit};
+use crate::ray::RayR3;
+use crate::roots;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::{Vec3, VecR3};
+use std::f64::consts::PI;
+
+/// A cylinder with hemispherical ends
+///
+/// All points within `radius` of the segment between two end points.
+/// Texture coordinates are the angle around the axis as a fraction of a full turn
+/// and the position along the axis as a fraction of the total length.
+pub struct Capsule<M: Material> {
+    /// Frame with its origin at the first end point and z along the segment
+    frame: Frame,
+    radius: f64,
+    length: f64,
+    material: M,
+}
+
+impl<M: Material> Capsule<M> {
+    /// Create a new capsule
+    ///
+    /// # Arguments
+    /// * `a` - Center of the first hemisphere.
+    /// * `b` - Center of the second hemisphere.
+    /// * `radius` - Radius of the cylinder and the hemispheres.
+    /// * `material` - The surface material.
+    pub fn new(a: VecR3, b: VecR3, radius: f64, material: M) -> Self {
+        Self {
+            frame: Frame::new(a, b - a),
+            radius,
+            length: (b - a).norm(),
+            material,
+        }
+    }
+
+    /// Intersect a ray in local coordinates.
+    fn hit_local(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<LocalHit> {
+        let mut closest: Option<LocalHit> = None;
+        let mut t_max = t_max;
+        let r = self.radius;
+        let o = ray.origin;
+        let d = ray.direction;
+
+        // Side, between the two end points
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - r * r;
+        if a > 0.0 {
+            if let Some((t0, t1)) = roots::quadratic(a, b, c) {
+                for &t in [t0, t1].iter() {
+                    let p = ray.at(t);
+                    if t < t_min || t > t_max || p.z < 0.0 || p.z > self.length {
+                        continue;
+                    }
+                    closest = Some(self.local_hit(t, Vec3::new(p.x, p.y, 0.0) / r, p.z));
+                    t_max = t;
+                    break;
+                }
+            }
+        }
+
+        // Hemispheres, each only beyond its end of the segment
+        for &(z, outside) in [(0.0, -1.0), (self.length, 1.0)].iter() {
+            let center = Vec3::new(0.0, 0.0, z);
+            let rel_origin = o - center;
+            let a = d.norm_squared();
+            let b = 2.0 * d.dot(rel_origin);
+            let c = rel_origin.norm_squared() - r * r;
+            if let Some((t0, t1)) = roots::quadratic(a, b, c) {
+                for &t in [t0, t1].iter() {
+                    let p = ray.at(t);
+                    if t < t_min || t > t_max || (p.z - z) * outside < 0.0 {
+                        continue;
+                    }
+                    closest = Some(self.local_hit(t, (p - center) / r, p.z));
+                    t_max = t;
+                    break;
+                }
+            }
+        }
+        closest
+    }
+
+    fn local_hit(&self, t: f64, normal: VecR3, z: f64) -> LocalHit {
+        LocalHit {
+            t,
+            normal,
+            uv: (
+                sweep_angle(normal) / (2.0 * PI),
+                (z + self.radius) / (self.length + 2.0 * self.radius),
+            ),
+        }
+    }
+}
+
+impl<M: Material> Surface for Capsule<M> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let hit = self.hit_local(&self.frame.ray_to_local(ray), t_min, t_max)?;
+        Some(hit.to_world(&self.frame, ray, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        let local = Aabb::new(Vec3::new(-r, -r, -r), Vec3::new(r, r, self.length + r));
+        Some(self.frame.bounding_box(&local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::materials::Lambertian;
+
+    fn capsule() -> Capsule<Lambertian> {
+        Capsule::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            0.5,
+            Lambertian::new(Colour::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    fn hit(surface: &dyn Surface, origin: VecR3, direction: VecR3) -> Option<Intersection<'_>> {
+        surface.intersect(&RayR3::new(origin, direction), 0.001, f64::INFINITY)
+    }
+
+    #[test]
+    fn side_hit() {
+        let capsule = capsule();
+        let hit = hit(
+            &capsule,
+            Vec3::new(0.0, 1.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        )
+        .unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9);
+        assert!(hit.front_face);
+        assert!((hit.normal - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn axial_rays_hit_ends() {
+        let capsule = capsule();
+        let top = hit(
+            &capsule,
+            Vec3::new(0.0, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((top.t - 2.5).abs() < 1e-9);
+        assert!((top.normal - Vec3::new(0.0, 1.0, 0.0)).norm() < 1e-9);
+        let bottom = hit(
+            &capsule,
+            Vec3::new(0.0, -5.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert!((bottom.t - 4.5).abs() < 1e-9);
+        assert!((bottom.normal - Vec3::new(0.0, -1.0, 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn hit_from_inside() {
+        let capsule = capsule();
+        let hit = hit(&capsule, Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-9);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn end_hit_off_axis() {
+        let capsule = capsule();
+        // Above the segment the surface is the hemisphere, not the cylinder
+        let hit = hit(
+            &capsule,
+            Vec3::new(0.0, 2.3, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        )
+        .unwrap();
+        assert!((hit.t - 4.6).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::aabb::Aabb;
+use crate::materials::Material;
+use crate::objects::frame::{intersect_cap, sweep_angle, sweep_radians, Frame, LocalHit};
+use crate::ray::RayR3;
+use crate::roots;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::{Vec3, VecR3};
+use std::f64::consts::PI;
+
+/// A cone with a circular base
+///
+/// The side has outward normals.
+/// The base is open unless closed by a cap with `with_caps`,
+/// and `with_sweep` leaves out part of the cone around its axis.
+/// Texture coordinates on the side are the fractions of the sweep and of the height;
+/// on the cap they are the fractions of the sweep and of the radius.
+pub struct Cone<M: Material> {
+    /// Frame with its origin at the center of the base and z towards the apex
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    /// Angle around the axis covered by the surface, in radians
+    sweep: f64,
+    caps: bool,
+    material: M,
+}
+
+impl<M: Material> Cone<M> {
+    /// Create a new cone
+    ///
+    /// # Arguments
+    /// * `base` - Center of the base.
+    /// * `apex` - The tip of the cone.
+    /// * `radius` - Radius of the base.
+    /// * `material` - The surface material.
+    pub fn new(base: VecR3, apex: VecR3, radius: f64, material: M) -> Self {
+        Self {
+            frame: Frame::new(base, apex - base),
+            radius,
+            height: (apex - base).norm(),
+            sweep: 2.0 * PI,
+            caps: false,
+            material,
+        }
+    }
+
+    /// Close the base with a flat cap.
+    pub fn with_caps(mut self) -> Self {
+        self.caps = true;
+        self
+    }
+
+    /// Only cover part of the way around the axis.
+    ///
+    /// # Arguments
+    /// * `degrees` - Angle covered by the surface, in (0, 360].
+    pub fn with_sweep(mut self, degrees: f64) -> Self {
+        self.sweep = sweep_radians(degrees);
+        self
+    }
+
+    /// Intersect a ray in local coordinates.
+    fn hit_local(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<LocalHit> {
+        let mut closest = None;
+        let o = ray.origin;
+        let d = ray.direction;
+        // Points on the side satisfy x^2 + y^2 = (k (h - z))^2
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let h = self.height - o.z;
+        let a = d.x * d.x + d.y * d.y - k2 * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y + k2 * h * d.z);
+        let c = o.x * o.x + o.y * o.y - k2 * h * h;
+        if let Some((t0, t1)) = roots::quadratic(a, b, c) {
+            for &t in [t0, t1].iter() {
+                if t < t_min || t > t_max {
+                    continue;
+                }
+                // The equation also describes a mirrored cone above the apex
+                let p = ray.at(t);
+                let phi = sweep_angle(p);
+                if p.z < 0.0 || p.z > self.height || phi > self.sweep {
+                    continue;
+                }
+                closest = Some(LocalHit {
+                    t,
+                    normal: Vec3::new(p.x, p.y, k2 * (self.height - p.z)).as_unit(),
+                    uv: (phi / self.sweep, p.z / self.height),
+                });
+                break;
+            }
+        }
+        if self.caps {
+            let t_max = closest.as_ref().map_or(t_max, |hit: &LocalHit| hit.t);
+            let cap = intersect_cap(ray, 0.0, -1.0, self.radius, self.sweep, t_min, t_max);
+            if cap.is_some() {
+                closest = cap;
+            }
+        }
+        closest
+    }
+}
+
+impl<M: Material> Surface for Cone<M> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let hit = self.hit_local(&self.frame.ray_to_local(ray), t_min, t_max)?;
+        Some(hit.to_world(&self.frame, ray, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        let local = Aabb::new(Vec3::new(-r, -r, 0.0), Vec3::new(r, r, self.height));
+        Some(self.frame.bounding_box(&local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::materials::Lambertian;
+
+    fn cone() -> Cone<Lambertian> {
+        Cone::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            1.0,
+            Lambertian::new(Colour::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    fn hit(surface: &dyn Surface, origin: VecR3, direction: VecR3) -> Option<Intersection<'_>> {
+        surface.intersect(&RayR3::new(origin, direction), 0.001, f64::INFINITY)
+    }
+
+    #[test]
+    fn side_hit() {
+        let cone = cone();
+        // The radius is 0.5 half way up
+        let hit = hit(&cone, Vec3::new(0.0, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9);
+        assert!(hit.front_face);
+        // The slope of the side is 2, so the normal is tilted up by atan(1/2)
+        let expected = Vec3::new(0.0, 1.0, 2.0).as_unit();
+        assert!((hit.normal - expected).norm() < 1e-9);
+    }
+
+    #[test]
+    fn axial_rays() {
+        let cone = cone();
+        let top = hit(&cone, Vec3::new(0.25, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!((top.t - 3.5).abs() < 1e-9);
+        assert!(top.front_face);
+        // The open base lets the ray through to the inside of the side
+        let bottom = hit(&cone, Vec3::new(0.25, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((bottom.t - 6.5).abs() < 1e-9);
+        assert!(!bottom.front_face);
+    }
+
+    #[test]
+    fn cap_closes_base() {
+        let cone = cone().with_caps();
+        let bottom = hit(&cone, Vec3::new(0.25, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((bottom.t - 5.0).abs() < 1e-9);
+        assert!(bottom.front_face);
+        assert!((bottom.normal - Vec3::new(0.0, -1.0, 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn mirrored_cone_above_apex_is_ignored() {
+        let cone = cone();
+        assert!(hit(&cone, Vec3::new(0.0, 3.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+    }
+
+    #[test]
+    fn grazing_rays() {
+        let cone = cone();
+        let x: f64 = 0.4999;
+        let near = hit(&cone, Vec3::new(x, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((near.t - (5.0 - (0.25 - x * x).sqrt())).abs() < 1e-6);
+        assert!(hit(
+            &cone,
+            Vec3::new(0.5001, 1.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0)
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,281 @@
+use crate::aabb::Aabb;
+use crate::ray::RayR3;
+use crate::surface::{Intersection, Surface};
+
+/// A boolean operation combining two solids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOperation {
+    /// Points inside either solid
+    Union,
+    /// Points inside both solids
+    Intersection,
+    /// Points inside the left solid but not the right solid
+    Difference,
+}
+
+impl CsgOperation {
+    /// Whether a point is inside the combined solid.
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// A solid built from two other solids by constructive solid geometry
+///
+/// Both surfaces must be closed with front faces pointing outwards, like `Sphere`, `Cuboid`,
+/// `Capsule`, `Torus`, capped cylinders and cones, or another `Csg`.
+/// Each is treated as the solid it encloses.
+/// The boundary of the result keeps the material and texture coordinates of the surface it
+/// comes from; where the right solid is subtracted its normals are flipped to face out of
+/// the result.
+///
+/// The combined solid is not sampled as a light, though emissive parts are still visible.
+pub struct Csg<A: Surface, B: Surface> {
+    operation: CsgOperation,
+    left: A,
+    right: B,
+}
+
+impl<A: Surface, B: Surface> Csg<A, B> {
+    /// Combine two solids
+    ///
+    /// # Arguments
+    /// * `operation` - The boolean operation.
+    /// * `left` - The first solid.
+    /// * `right` - The second solid; subtracted from `left` by `CsgOperation::Difference`.
+    pub fn new(operation: CsgOperation, left: A, right: B) -> Self {
+        Self {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    /// Points inside either solid.
+    pub fn union(left: A, right: B) -> Self {
+        Self::new(CsgOperation::Union, left, right)
+    }
+
+    /// Points inside both solids.
+    pub fn intersection(left: A, right: B) -> Self {
+        Self::new(CsgOperation::Intersection, left, right)
+    }
+
+    /// Points inside `left` but not inside `right`.
+    pub fn difference(left: A, right: B) -> Self {
+        Self::new(CsgOperation::Difference, left, right)
+    }
+
+    pub fn operation(&self) -> CsgOperation {
+        self.operation
+    }
+}
+
+/// The state of the ray relative to one of the solids in a `Csg`
+struct Operand<'a> {
+    surface: &'a dyn Surface,
+    /// The next crossing of the surface along the ray
+    next: Option<Intersection<'a>>,
+    /// Whether the ray is currently inside the solid
+    inside: bool,
+}
+
+impl<'a> Operand<'a> {
+    fn new(surface: &'a dyn Surface, ray: &RayR3, t_min: f64) -> Self {
+        let next = surface.intersect(ray, t_min, f64::INFINITY);
+        // A closed surface is first crossed from the inside if the ray starts inside it
+        let inside = next.as_ref().is_some_and(|hit| !hit.front_face);
+        Self {
+            surface,
+            next,
+            inside,
+        }
+    }
+
+    /// Cross the next intersection and find the one after it.
+    fn advance(&mut self, ray: &RayR3) -> Intersection<'a> {
+        let hit = self.next.take().expect("no intersection to advance past");
+        self.inside = hit.front_face;
+        // Step just past the crossing so that it is not found again
+        let t_min = hit.t + 1e-9 * hit.t.abs().max(1.0);
+        self.next = self.surface.intersect(ray, t_min, f64::INFINITY);
+        hit
+    }
+}
+
+impl<A: Surface, B: Surface> Surface for Csg<A, B> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let mut left = Operand::new(&self.left, ray, t_min);
+        let mut right = Operand::new(&self.right, ray, t_min);
+        let mut inside = self.operation.contains(left.inside, right.inside);
+
+        // Walk through the crossings of both surfaces in order along the ray
+        // until the ray enters or leaves the combined solid.
+        loop {
+            let from_left = match (&left.next, &right.next) {
+                (Some(l), Some(r)) => l.t <= r.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+            let operand = if from_left { &mut left } else { &mut right };
+            if operand.next.as_ref().is_none_or(|hit| hit.t > t_max) {
+                return None;
+            }
+            let mut hit = operand.advance(ray);
+
+            let was_inside = inside;
+            inside = self.operation.contains(left.inside, right.inside);
+            if inside != was_inside {
+                if !from_left && self.operation == CsgOperation::Difference {
+                    // Entering the right solid leaves the result and vice versa
+                    hit.front_face = !hit.front_face;
+                }
+                // The light that was hit may be partly removed, so it cannot be sampled
+                hit.light = None;
+                return Some(hit);
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let left = self.left.bounding_box();
+        let right = self.right.bounding_box();
+        match self.operation {
+            CsgOperation::Union => Some(left?.union(&right?)),
+            CsgOperation::Intersection => match (left, right) {
+                (Some(l), Some(r)) => {
+                    let bounds = l.intersection(&r);
+                    // An empty result has no intersections; keep a valid box for the BVH
+                    Some(if bounds.is_empty() { l } else { bounds })
+                }
+                (l, r) => l.or(r),
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::materials::DiffuseLight;
+    use crate::objects::Sphere;
+    use crate::vec3::Vec3;
+
+    type Solid = Sphere<DiffuseLight>;
+
+    /// Unit spheres centered at x = 0 and x = 1, told apart by their emission
+    fn spheres() -> (Solid, Solid) {
+        (
+            Sphere::new(
+                Vec3::new(0.0, 0.0, 0.0),
+                1.0,
+                DiffuseLight::new(Colour::new(1.0, 0.0, 0.0)),
+            ),
+            Sphere::new(
+                Vec3::new(1.0, 0.0, 0.0),
+                1.0,
+                DiffuseLight::new(Colour::new(0.0, 1.0, 0.0)),
+            ),
+        )
+    }
+
+    /// Intersect a ray along the x axis.
+    ///
+    /// # Returns
+    /// The ray position, whether it enters the solid, and whether it hit the left sphere.
+    fn hit(csg: &Csg<Solid, Solid>, x: f64, direction: f64) -> Option<(f64, bool, bool)> {
+        let ray = RayR3::new(Vec3::new(x, 0.0, 0.0), Vec3::new(direction, 0.0, 0.0));
+        let hit = csg.intersect(&ray, 0.001, f64::INFINITY)?;
+        // The normal always faces the ray
+        assert!(hit.normal.x * direction < 0.0);
+        assert!(hit.light.is_none());
+        let from_left = hit.material.emitted(&hit.point, true).x > 0.0;
+        Some((hit.t, hit.front_face, from_left))
+    }
+
+    fn assert_hit(actual: Option<(f64, bool, bool)>, t: f64, front_face: bool, from_left: bool) {
+        let (actual_t, actual_front, actual_left) = actual.expect("ray should hit the solid");
+        assert!(
+            (actual_t - t).abs() < 1e-9,
+            "hit at t = {}, expected {}",
+            actual_t,
+            t
+        );
+        assert_eq!(actual_front, front_face);
+        assert_eq!(actual_left, from_left);
+    }
+
+    #[test]
+    fn union() {
+        let (a, b) = spheres();
+        let csg = Csg::union(a, b);
+        assert_hit(hit(&csg, -5.0, 1.0), 4.0, true, true);
+        assert_hit(hit(&csg, 5.0, -1.0), 3.0, true, false);
+        // The inner boundaries are not part of the union
+        assert_hit(hit(&csg, 0.5, 1.0), 1.5, false, false);
+        assert_hit(hit(&csg, 0.5, -1.0), 1.5, false, true);
+    }
+
+    #[test]
+    fn intersection() {
+        let (a, b) = spheres();
+        let csg = Csg::intersection(a, b);
+        assert_hit(hit(&csg, -5.0, 1.0), 5.0, true, false);
+        assert_hit(hit(&csg, 5.0, -1.0), 4.0, true, true);
+        assert_hit(hit(&csg, 0.5, 1.0), 0.5, false, true);
+        // Inside the left sphere only; not inside the intersection yet
+        assert_hit(hit(&csg, -0.5, 1.0), 0.5, true, false);
+    }
+
+    #[test]
+    fn difference() {
+        let (a, b) = spheres();
+        let csg = Csg::difference(a, b);
+        assert_hit(hit(&csg, -5.0, 1.0), 4.0, true, true);
+        // Leaving the result by entering the right sphere
+        assert_hit(hit(&csg, -0.5, 1.0), 0.5, false, false);
+        // Entering the result by leaving the right sphere
+        assert_hit(hit(&csg, 5.0, -1.0), 5.0, true, false);
+        // Inside the right sphere the ray is outside the result until it leaves
+        assert_hit(hit(&csg, 0.5, -1.0), 0.5, true, false);
+    }
+
+    #[test]
+    fn misses() {
+        let (a, b) = spheres();
+        let csg = Csg::intersection(a, b);
+        let ray = RayR3::new(Vec3::new(-5.0, 0.95, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(csg.intersect(&ray, 0.001, f64::INFINITY).is_none());
+        // A hit beyond t_max is not reported
+        let ray = RayR3::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(csg.intersect(&ray, 0.001, 4.5).is_none());
+    }
+
+    #[test]
+    fn nested() {
+        let (a, b) = spheres();
+        let c = Sphere::new(
+            Vec3::new(0.5, 0.0, 0.0),
+            0.25,
+            DiffuseLight::new(Colour::new(0.0, 0.0, 1.0)),
+        );
+        let csg = Csg::difference(Csg::intersection(a, b), c);
+        let ray = RayR3::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let first = csg.intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((first.t - 5.0).abs() < 1e-9);
+        assert!(first.front_face);
+        let second = csg.intersect(&ray, first.t + 1e-6, f64::INFINITY).unwrap();
+        assert!((second.t - 5.25).abs() < 1e-9);
+        assert!(!second.front_face);
+        let third = csg.intersect(&ray, second.t + 1e-6, f64::INFINITY).unwrap();
+        assert!((third.t - 5.75).abs() < 1e-9);
+        assert!(third.front_face);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::aabb::Aabb;
+use crate::materials::Material;
+use crate::objects::Quad;
+use crate::ray::RayR3;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::{Vec3, VecR3};
+use std::sync::Arc;
+
+/// An axis-aligned box made of six quads
+///
+/// The normals of the faces point outwards.
+/// Each face has its own texture coordinates from 0 to 1 along its edges.
+pub struct Cuboid {
+    bounds: Aabb,
+    /// The faces, shared so that they can be sampled individually if they are lights
+    faces: Vec<Arc<dyn Surface>>,
+}
+
+impl Cuboid {
+    /// Create a new box
+    ///
+    /// # Arguments
+    /// * `a` - One corner of the box.
+    /// * `b` - The opposite corner of the box.
+    /// * `material` - The material of every face.
+    pub fn new<M: Material + 'static>(a: VecR3, b: VecR3, material: M) -> Self {
+        let bounds = Aabb::from_points(a, b);
+        let (min, max) = (bounds.min, bounds.max);
+        let d = bounds.diagonal();
+        let dx = Vec3::new(d.x, 0.0, 0.0);
+        let dy = Vec3::new(0.0, d.y, 0.0);
+        let dz = Vec3::new(0.0, 0.0, d.z);
+
+        let material = Arc::new(material);
+        let quad = |corner: VecR3, u: VecR3, v: VecR3| -> Arc<dyn Surface> {
+            Arc::new(Quad::new(corner, u, v, Arc::clone(&material)))
+        };
+        let faces = vec![
+            // Front (+z), right (+x), back (-z) and left (-x)
+            quad(Vec3::new(min.x, min.y, max.z), dx, dy),
+            quad(Vec3::new(max.x, min.y, max.z), -dz, dy),
+            quad(Vec3::new(max.x, min.y, min.z), -dx, dy),
+            quad(Vec3::new(min.x, min.y, min.z), dz, dy),
+            // Top (+y) and bottom (-y)
+            quad(Vec3::new(min.x, max.y, max.z), dx, -dz),
+            quad(Vec3::new(min.x, min.y, min.z), dx, dz),
+        ];
+        Self { bounds, faces }
+    }
+}
+
+impl Surface for Cuboid {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let mut result = None;
+        let mut closest_so_far = t_max;
+        for face in &self.faces {
+            if let Some(intersection) = face.intersect(ray, t_min, closest_so_far) {
+                closest_so_far = intersection.t;
+                result = Some(intersection);
+            }
+        }
+        result
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Surface>> {
+        self.faces
+            .iter()
+            .filter(|face| face.is_light())
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,245 @@
+use crate::aabb::Aabb;
+use crate::materials::Material;
+use crate::objects::frame::{intersect_cap, sweep_angle, sweep_radians, Frame, LocalHit};
+use crate::ray::RayR3;
+use crate::roots;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::{Vec3, VecR3};
+use std::f64::consts::PI;
+
+/// A cylinder around an axis
+///
+/// The side has outward normals.
+/// The ends are open unless closed by caps with `with_caps`,
+/// and `with_sweep` leaves out part of the cylinder around its axis.
+/// Texture coordinates on the side are the fractions of the sweep and of the height;
+/// on the caps they are the fractions of the sweep and of the radius.
+pub struct Cylinder<M: Material> {
+    /// Frame with its origin at the center of the base and z along the axis
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    /// Angle around the axis covered by the surface, in radians
+    sweep: f64,
+    caps: bool,
+    material: M,
+}
+
+impl<M: Material> Cylinder<M> {
+    /// Create a new cylinder
+    ///
+    /// # Arguments
+    /// * `base` - Center of the bottom end.
+    /// * `top` - Center of the top end.
+    /// * `radius` - The cylinder radius.
+    /// * `material` - The surface material.
+    pub fn new(base: VecR3, top: VecR3, radius: f64, material: M) -> Self {
+        Self {
+            frame: Frame::new(base, top - base),
+            radius,
+            height: (top - base).norm(),
+            sweep: 2.0 * PI,
+            caps: false,
+            material,
+        }
+    }
+
+    /// Close the ends with flat caps.
+    pub fn with_caps(mut self) -> Self {
+        self.caps = true;
+        self
+    }
+
+    /// Only cover part of the way around the axis.
+    ///
+    /// # Arguments
+    /// * `degrees` - Angle covered by the surface, in (0, 360].
+    pub fn with_sweep(mut self, degrees: f64) -> Self {
+        self.sweep = sweep_radians(degrees);
+        self
+    }
+
+    /// Intersect a ray in local coordinates.
+    fn hit_local(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<LocalHit> {
+        let mut closest = None;
+        let o = ray.origin;
+        let d = ray.direction;
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+        if let Some((t0, t1)) = roots::quadratic(a, b, c) {
+            for &t in [t0, t1].iter() {
+                if t < t_min || t > t_max {
+                    continue;
+                }
+                let p = ray.at(t);
+                let phi = sweep_angle(p);
+                if p.z < 0.0 || p.z > self.height || phi > self.sweep {
+                    continue;
+                }
+                closest = Some(LocalHit {
+                    t,
+                    normal: Vec3::new(p.x, p.y, 0.0) / self.radius,
+                    uv: (phi / self.sweep, p.z / self.height),
+                });
+                break;
+            }
+        }
+        if self.caps {
+            for &(z, normal) in [(0.0, -1.0), (self.height, 1.0)].iter() {
+                let t_max = closest.as_ref().map_or(t_max, |hit: &LocalHit| hit.t);
+                let cap = intersect_cap(ray, z, normal, self.radius, self.sweep, t_min, t_max);
+                if cap.is_some() {
+                    closest = cap;
+                }
+            }
+        }
+        closest
+    }
+}
+
+impl<M: Material> Surface for Cylinder<M> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let hit = self.hit_local(&self.frame.ray_to_local(ray), t_min, t_max)?;
+        Some(hit.to_world(&self.frame, ray, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        let local = Aabb::new(Vec3::new(-r, -r, 0.0), Vec3::new(r, r, self.height));
+        Some(self.frame.bounding_box(&local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::materials::Lambertian;
+
+    fn cylinder() -> Cylinder<Lambertian> {
+        Cylinder::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            1.0,
+            Lambertian::new(Colour::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    fn hit(surface: &dyn Surface, origin: VecR3, direction: VecR3) -> Option<Intersection<'_>> {
+        surface.intersect(&RayR3::new(origin, direction), 0.001, f64::INFINITY)
+    }
+
+    #[test]
+    fn side_hit() {
+        let cylinder = cylinder();
+        let hit = hit(
+            &cylinder,
+            Vec3::new(0.0, 1.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        )
+        .unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-9);
+        assert!(hit.front_face);
+        assert!((hit.normal - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-9);
+        assert!((hit.uv.1 - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn side_hit_from_inside() {
+        let cylinder = cylinder();
+        let hit = hit(
+            &cylinder,
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((hit.t - 1.0).abs() < 1e-9);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn axial_ray_through_open_ends() {
+        let cylinder = cylinder();
+        assert!(hit(
+            &cylinder,
+            Vec3::new(0.2, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn axial_ray_hits_caps() {
+        let cylinder = cylinder().with_caps();
+        let top = hit(
+            &cylinder,
+            Vec3::new(0.2, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert!((top.t - 3.0).abs() < 1e-9);
+        assert!(top.front_face);
+        assert!((top.normal - Vec3::new(0.0, 1.0, 0.0)).norm() < 1e-9);
+        let bottom = hit(
+            &cylinder,
+            Vec3::new(0.2, -5.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert!((bottom.t - 5.0).abs() < 1e-9);
+        assert!(bottom.front_face);
+        assert!((bottom.normal - Vec3::new(0.0, -1.0, 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn grazing_rays() {
+        let cylinder = cylinder();
+        let x: f64 = 0.9999;
+        let hit_ray = hit(&cylinder, Vec3::new(x, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((hit_ray.t - (5.0 - (1.0 - x * x).sqrt())).abs() < 1e-9);
+        assert!(hit(
+            &cylinder,
+            Vec3::new(1.0001, 1.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn ray_beyond_ends_misses() {
+        let cylinder = cylinder();
+        assert!(hit(
+            &cylinder,
+            Vec3::new(0.0, 2.5, 5.0),
+            Vec3::new(0.0, 0.0, -1.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn half_sweep_keeps_one_side() {
+        let cylinder = cylinder().with_sweep(180.0);
+        // Each ray crosses the side twice, once in each half. Only one of the crossings remains,
+        // which one ray meets first and the other meets last.
+        let front = hit(
+            &cylinder,
+            Vec3::new(5.0, 1.0, 0.3),
+            Vec3::new(-1.0, 0.0, 0.0),
+        );
+        let back = hit(
+            &cylinder,
+            Vec3::new(-5.0, 1.0, 0.3),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+        let (front, back) = (front.unwrap(), back.unwrap());
+        assert!((front.t - back.t).abs() > 1.0);
+    }
+
+    #[test]
+    fn bounding_box_contains_cylinder() {
+        let bounds = cylinder().bounding_box().unwrap();
+        assert!((bounds.min - Vec3::new(-1.0, 0.0, -1.0)).norm() < 1e-9);
+        assert!((bounds.max - Vec3::new(1.0, 2.0, 1.0)).norm() < 1e-9);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::aabb::Aabb;
+use crate::lights::{flat_light_pdf, sample_flat_light, LightSample};
+use crate::materials::Material;
+use crate::ray::RayR3;
+use crate::sampling::orthonormal_basis;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::VecR3;
+use std::f64::consts::PI;
+
+/// A flat circular disk
+///
+/// The front face is the side towards which `normal` points.
+/// Texture coordinates are polar: u in [0, 1) is the angle around the normal and
+/// v in [0, 1] is the distance from the center as a fraction of the radius.
+pub struct Disk<M: Material> {
+    center: VecR3,
+    normal: VecR3,
+    radius: f64,
+    /// Unit vectors in the plane of the disk with `u_axis.cross(v_axis) == normal`
+    u_axis: VecR3,
+    v_axis: VecR3,
+    material: M,
+}
+
+impl<M: Material> Disk<M> {
+    /// Create a new disk
+    ///
+    /// # Arguments
+    /// * `center` - The center of the disk.
+    /// * `normal` - Normal of the front face; need not be unit length.
+    /// * `radius` - The disk radius.
+    /// * `material` - The surface material.
+    pub fn new(center: VecR3, normal: VecR3, radius: f64, material: M) -> Self {
+        let normal = normal.as_unit();
+        let (a, b) = orthonormal_basis(&normal);
+        Self {
+            center,
+            normal,
+            radius,
+            u_axis: b,
+            v_axis: a,
+            material,
+        }
+    }
+
+    /// Intersect a ray with the disk.
+    ///
+    /// # Returns
+    /// The ray position and texture coordinates of the intersection.
+    fn hit(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+        let denominator = self.normal.dot(ray.direction);
+        // The ray is parallel to the plane
+        if denominator.abs() <= 1e-12 * ray.direction.norm() {
+            return None;
+        }
+        let t = self.normal.dot(self.center - ray.origin) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let offset = ray.at(t) - self.center;
+        let distance_squared = offset.norm_squared();
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+        let phi = offset.dot(self.v_axis).atan2(offset.dot(self.u_axis));
+        let u = phi.rem_euclid(2.0 * PI) / (2.0 * PI);
+        let v = distance_squared.sqrt() / self.radius;
+        Some((t, u, v))
+    }
+
+    fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+}
+
+impl<M: Material> Surface for Disk<M> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let (t, u, v) = self.hit(ray, t_min, t_max)?;
+        let intersection =
+            Intersection::new(ray, t, ray.at(t), self.normal, &self.material).with_uv(u, v);
+        if self.is_light() {
+            Some(intersection.with_light(self))
+        } else {
+            Some(intersection)
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // The extent along each axis is the radius times the sine of its angle to the normal
+        let extent = self
+            .normal
+            .map(|n| self.radius * (1.0 - n * n).max(0.0).sqrt());
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Samples points uniformly by area.
+    fn sample(&self, origin: &VecR3, u: (f64, f64)) -> Option<LightSample> {
+        let r = self.radius * u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let point = self.center + self.u_axis * (r * phi.cos()) + self.v_axis * (r * phi.sin());
+        sample_flat_light(origin, point, self.normal, self.area(), &self.material)
+    }
+
+    fn pdf(&self, origin: &VecR3, direction: &VecR3) -> f64 {
+        let ray = RayR3::new(*origin, direction.as_unit());
+        match self.hit(&ray, 0.0, f64::INFINITY) {
+            Some((t, _, _)) => flat_light_pdf(t, ray.direction.dot(self.normal), self.area()),
+            None => 0.0,
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::aabb::Aabb;
+use crate::materials::Material;
+use crate::ray::RayR3;
+use crate::sampling::orthonormal_basis;
+use crate::surface::Intersection;
+use crate::vec3::{Vec3, VecR3};
+use std::f64::consts::PI;
+
+/// An orthonormal coordinate frame in which an object is defined
+///
+/// Primitives with an axis of symmetry are intersected in a frame whose z axis is that axis.
+/// The frame does not scale, so ray positions are the same in both coordinate systems.
+pub(crate) struct Frame {
+    origin: VecR3,
+    x: VecR3,
+    y: VecR3,
+    z: VecR3,
+}
+
+impl Frame {
+    /// Create a frame with its origin at `origin` and its z axis along `axis`.
+    pub fn new(origin: VecR3, axis: VecR3) -> Self {
+        let z = axis.as_unit();
+        let (a, b) = orthonormal_basis(&z);
+        // b.cross(a) == z so that the frame is right handed
+        Self {
+            origin,
+            x: b,
+            y: a,
+            z,
+        }
+    }
+
+    pub fn vector_to_local(&self, v: VecR3) -> VecR3 {
+        Vec3::new(v.dot(self.x), v.dot(self.y), v.dot(self.z))
+    }
+
+    pub fn point_to_local(&self, p: VecR3) -> VecR3 {
+        self.vector_to_local(p - self.origin)
+    }
+
+    pub fn vector_to_world(&self, v: VecR3) -> VecR3 {
+        self.x * v.x + self.y * v.y + self.z * v.z
+    }
+
+    pub fn ray_to_local(&self, ray: &RayR3) -> RayR3 {
+        RayR3::new(
+            self.point_to_local(ray.origin),
+            self.vector_to_local(ray.direction),
+        )
+    }
+
+    /// A world space bounding box containing a local bounding box.
+    pub fn bounding_box(&self, local: &Aabb) -> Aabb {
+        // The extent of the box along each world axis
+        let half = local.diagonal() / 2.0;
+        let extent = Vec3::new(
+            half.x * self.x.x.abs() + half.y * self.y.x.abs() + half.z * self.z.x.abs(),
+            half.x * self.x.y.abs() + half.y * self.y.y.abs() + half.z * self.z.y.abs(),
+            half.x * self.x.z.abs() + half.y * self.y.z.abs() + half.z * self.z.z.abs(),
+        );
+        let center = self.origin + self.vector_to_world(local.centroid());
+        Aabb::new(center - extent, center + extent)
+    }
+}
+
+/// An intersection in the local coordinates of a `Frame`
+pub(crate) struct LocalHit {
+    pub t: f64,
+    /// Outward unit normal in local coordinates
+    pub normal: VecR3,
+    pub uv: (f64, f64),
+}
+
+impl LocalHit {
+    /// Convert to an intersection in world coordinates.
+    pub fn to_world<'a>(
+        &self,
+        frame: &Frame,
+        ray: &RayR3,
+        material: &'a dyn Material,
+    ) -> Intersection<'a> {
+        let normal = frame.vector_to_world(self.normal);
+        Intersection::new(ray, self.t, ray.at(self.t), normal, material)
+            .with_uv(self.uv.0, self.uv.1)
+    }
+}
+
+/// Convert a sweep angle to radians, clamping it to (0, 2 pi].
+pub(crate) fn sweep_radians(degrees: f64) -> f64 {
+    degrees.to_radians().clamp(f64::MIN_POSITIVE, 2.0 * PI)
+}
+
+/// Angle of a local point around the z axis in [0, 2 pi), counter-clockwise from x.
+pub(crate) fn sweep_angle(p: VecR3) -> f64 {
+    p.y.atan2(p.x).rem_euclid(2.0 * PI)
+}
+
+/// Intersect a local ray with a disk-shaped cap perpendicular to the z axis.
+///
+/// # Arguments
+/// * `z` - Position of the cap along the axis.
+/// * `normal` - Direction of the outward normal along z; 1 or -1.
+/// * `radius` - Radius of the cap.
+/// * `sweep` - Angle covered by the cap around the axis in radians.
+pub(crate) fn intersect_cap(
+    ray: &RayR3,
+    z: f64,
+    normal: f64,
+    radius: f64,
+    sweep: f64,
+    t_min: f64,
+    t_max: f64,
+) -> Option<LocalHit> {
+    if ray.direction.z == 0.0 {
+        return None;
+    }
+    let t = (z - ray.origin.z) / ray.direction.z;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    let p = ray.at(t);
+    let distance_squared = p.x * p.x + p.y * p.y;
+    if distance_squared > radius * radius {
+        return None;
+    }
+    let phi = sweep_angle(p);
+    if phi > sweep {
+        return None;
+    }
+    Some(LocalHit {
+        t,
+        normal: Vec3::new(0.0, 0.0, normal),
+        uv: (phi / sweep, distance_squared.sqrt() / radius),
+    })
+}
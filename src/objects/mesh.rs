@@ -0,0 +1,251 @@
+use crate::aabb::Aabb;
+use crate::bvh::BvhTree;
+use crate::materials::Material;
+use crate::objects::triangle::{intersect_triangle, Triangle};
+use crate::ray::RayR3;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::VecR3;
+use std::sync::Arc;
+
+/// Vertex indices of a triangle mesh face
+///
+/// Each vertex attribute is indexed separately into the corresponding mesh buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct Face {
+    /// Indices into the mesh positions
+    pub positions: [usize; 3],
+    /// Indices into the mesh normals, if the face has per-vertex shading normals.
+    pub normals: Option<[usize; 3]>,
+    /// Indices into the mesh texture coordinates, if the face has them.
+    pub uvs: Option<[usize; 3]>,
+}
+
+impl Face {
+    /// A face with positions only
+    pub fn new(positions: [usize; 3]) -> Self {
+        Self {
+            positions,
+            normals: None,
+            uvs: None,
+        }
+    }
+}
+
+/// An indexed triangle mesh
+///
+/// Vertex positions, normals and texture coordinates are stored in shared buffers
+/// and referenced by index from each face. All faces share a single material.
+///
+/// If the material is emissive each face is sampled as a separate light.
+pub struct TriangleMesh<M: Material> {
+    positions: Vec<VecR3>,
+    normals: Vec<VecR3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<Face>,
+    material: Arc<M>,
+    tree: BvhTree,
+    /// A triangle for each face if the mesh is a light, so that faces can be sampled
+    face_lights: Vec<Arc<dyn Surface>>,
+}
+
+impl<M: Material + 'static> TriangleMesh<M> {
+    /// Create a new triangle mesh
+    ///
+    /// # Arguments
+    /// * `positions` - Vertex positions.
+    /// * `normals` - Vertex shading normals. Need not be unit length.
+    /// * `uvs` - Vertex texture coordinates.
+    /// * `faces` - Mesh triangles as indices into the vertex buffers.
+    ///   The front face is the side from which the vertices appear counter-clockwise.
+    /// * `material` - Material shared by every face.
+    ///
+    /// # Panics
+    /// If any face index is out of bounds for its buffer.
+    pub fn new(
+        positions: Vec<VecR3>,
+        normals: Vec<VecR3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<Face>,
+        material: M,
+    ) -> Self {
+        for face in &faces {
+            assert!(face.positions.iter().all(|&i| i < positions.len()));
+            if let Some(indices) = face.normals {
+                assert!(indices.iter().all(|&i| i < normals.len()));
+            }
+            if let Some(indices) = face.uvs {
+                assert!(indices.iter().all(|&i| i < uvs.len()));
+            }
+        }
+        let bounds: Vec<Aabb> = faces
+            .iter()
+            .map(|face| {
+                let [a, b, c] = face.positions;
+                Aabb::from_points(positions[a], positions[b]).grow(positions[c])
+            })
+            .collect();
+        let tree = BvhTree::new(&bounds);
+        let material = Arc::new(material);
+        let face_lights = if material.is_emissive() {
+            faces
+                .iter()
+                .map(|face| -> Arc<dyn Surface> {
+                    let [a, b, c] = face.positions;
+                    Arc::new(Triangle::new(
+                        positions[a],
+                        positions[b],
+                        positions[c],
+                        Arc::clone(&material),
+                    ))
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Self {
+            positions,
+            normals,
+            uvs,
+            faces,
+            material,
+            tree,
+            face_lights,
+        }
+    }
+}
+
+impl<M: Material> TriangleMesh<M> {
+    /// Number of triangles in the mesh
+    pub fn num_faces(&self) -> usize {
+        self.faces.len()
+    }
+
+    /// Intersect a ray with a single face of the mesh.
+    fn intersect_face(
+        &self,
+        index: usize,
+        ray: &RayR3,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<Intersection<'_>> {
+        let face = &self.faces[index];
+        let [a, b, c] = face.positions;
+        let (a, b, c) = (self.positions[a], self.positions[b], self.positions[c]);
+        let (t, b1, b2) = intersect_triangle(ray, a, b, c, t_min, t_max)?;
+        let b0 = 1.0 - b1 - b2;
+
+        let front_normal = (b - a).cross(c - a).as_unit();
+        let mut intersection = Intersection::new(ray, t, ray.at(t), front_normal, &*self.material);
+        intersection = match face.uvs {
+            Some([i, j, k]) => {
+                let (u0, v0) = self.uvs[i];
+                let (u1, v1) = self.uvs[j];
+                let (u2, v2) = self.uvs[k];
+                intersection.with_uv(b0 * u0 + b1 * u1 + b2 * u2, b0 * v0 + b1 * v1 + b2 * v2)
+            }
+            None => intersection.with_uv(b1, b2),
+        };
+        if let Some([i, j, k]) = face.normals {
+            let shading_normal = self.normals[i] * b0 + self.normals[j] * b1 + self.normals[k] * b2;
+            if !shading_normal.near_zero() {
+                intersection = intersection.with_shading_normal(shading_normal);
+            }
+        }
+        if let Some(light) = self.face_lights.get(index) {
+            intersection = intersection.with_light(&**light);
+        }
+        Some(intersection)
+    }
+}
+
+impl<M: Material> Surface for TriangleMesh<M> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        self.tree.intersect(ray, t_min, t_max, |i, t_max| {
+            self.intersect_face(i, ray, t_min, t_max)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.tree.bounds().unwrap_or_else(Aabb::empty))
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Surface>> {
+        self.face_lights.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::materials::{DiffuseLight, Lambertian};
+    use crate::vec3::Vec3;
+
+    fn square<M: Material + 'static>(material: M) -> TriangleMesh<M> {
+        let positions = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let faces = vec![Face::new([0, 1, 2]), Face::new([0, 2, 3])];
+        TriangleMesh::new(positions, Vec::new(), Vec::new(), faces, material)
+    }
+
+    #[test]
+    fn emissive_faces_are_lights() {
+        let mesh = square(DiffuseLight::new(Colour::new(1.0, 1.0, 1.0)));
+        let lights = mesh.lights();
+        assert_eq!(lights.len(), 2);
+
+        // Hits report the light of the face that was hit
+        let ray = RayR3::new(Vec3::new(0.8, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = mesh.intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        let light = hit.light.expect("hit should record the light");
+        let address = |surface: &dyn Surface| surface as *const dyn Surface as *const u8;
+        assert_eq!(address(light), address(&*lights[0]));
+    }
+
+    #[test]
+    fn non_emissive_mesh_has_no_lights() {
+        let mesh = square(Lambertian::new(Colour::new(0.5, 0.5, 0.5)));
+        assert!(mesh.lights().is_empty());
+        let ray = RayR3::new(Vec3::new(0.8, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh
+            .intersect(&ray, 0.001, f64::INFINITY)
+            .unwrap()
+            .light
+            .is_none());
+    }
+
+    #[test]
+    fn shading_normal_keeps_geometric_normal() {
+        let positions = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let normals = vec![Vec3::new(1.0, 0.0, 1.0)];
+        let face = Face {
+            normals: Some([0, 0, 0]),
+            ..Face::new([0, 1, 2])
+        };
+        let mesh = TriangleMesh::new(
+            positions,
+            normals,
+            Vec::new(),
+            vec![face],
+            Lambertian::new(Colour::new(0.5, 0.5, 0.5)),
+        );
+        let ray = RayR3::new(Vec3::new(0.2, 0.2, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = mesh.intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!(!hit.front_face);
+        assert!((hit.normal - Vec3::new(0.0, 0.0, -1.0)).norm() < 1e-9);
+        let expected = -Vec3::new(1.0, 0.0, 1.0).as_unit();
+        assert!((hit.shading_normal - expected).norm() < 1e-9);
+
+        // Below the geometric surface but above the shading one
+        assert!(hit.same_side(&Vec3::new(0.0, 0.0, -1.0)));
+        assert!(!hit.same_side(&Vec3::new(-1.0, 0.0, 0.1)));
+    }
+}
@@ -1,3 +1,30 @@
+pub mod capsule;
+pub mod cone;
+pub mod csg;
+pub mod cuboid;
+pub mod cylinder;
+pub mod disk;
+mod frame;
+pub mod mesh;
+pub mod plane;
+pub mod quad;
+pub mod sdf;
 pub mod sphere;
+pub mod torus;
+pub mod transformed;
+pub mod triangle;
 
+pub use capsule::Capsule;
+pub use cone::Cone;
+pub use csg::{Csg, CsgOperation};
+pub use cuboid::Cuboid;
+pub use cylinder::Cylinder;
+pub use disk::Disk;
+pub use mesh::{Face, TriangleMesh};
+pub use plane::Plane;
+pub use quad::Quad;
+pub use sdf::Sdf;
 pub use sphere::Sphere;
+pub use torus::Torus;
+pub use transformed::Transformed;
+pub use triangle::Triangle;
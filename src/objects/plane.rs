@@ -0,0 +1,63 @@
+use crate::aabb::Aabb;
+use crate::materials::Material;
+use crate::ray::RayR3;
+use crate::sampling::orthonormal_basis;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::VecR3;
+
+/// An infinite plane
+///
+/// The front face is the side towards which `normal` points.
+/// Texture coordinates repeat every unit of distance along two perpendicular axes
+/// in the plane.
+pub struct Plane<M: Material> {
+    point: VecR3,
+    normal: VecR3,
+    /// Unit vectors in the plane with `u_axis.cross(v_axis) == normal`
+    u_axis: VecR3,
+    v_axis: VecR3,
+    material: M,
+}
+
+impl<M: Material> Plane<M> {
+    /// Create a new plane
+    ///
+    /// # Arguments
+    /// * `point` - Any point on the plane; the origin of the texture coordinates.
+    /// * `normal` - Normal of the front face; need not be unit length.
+    /// * `material` - The surface material.
+    pub fn new(point: VecR3, normal: VecR3, material: M) -> Self {
+        let normal = normal.as_unit();
+        let (a, b) = orthonormal_basis(&normal);
+        Self {
+            point,
+            normal,
+            u_axis: b,
+            v_axis: a,
+            material,
+        }
+    }
+}
+
+impl<M: Material> Surface for Plane<M> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let denominator = self.normal.dot(ray.direction);
+        // The ray is parallel to the plane
+        if denominator.abs() <= 1e-12 * ray.direction.norm() {
+            return None;
+        }
+        let t = self.normal.dot(self.point - ray.origin) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let point = ray.at(t);
+        let offset = point - self.point;
+        let u = offset.dot(self.u_axis).rem_euclid(1.0);
+        let v = offset.dot(self.v_axis).rem_euclid(1.0);
+        Some(Intersection::new(ray, t, point, self.normal, &self.material).with_uv(u, v))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
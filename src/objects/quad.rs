@@ -0,0 +1,109 @@
+use crate::aabb::Aabb;
+use crate::lights::{flat_light_pdf, sample_flat_light, LightSample};
+use crate::materials::Material;
+use crate::ray::RayR3;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::VecR3;
+
+/// A parallelogram
+///
+/// Spanned by two edges `u` and `v` from a corner.
+/// The front face is the side towards which `u.cross(v)` points.
+/// Texture coordinates are the fractions of the way along `u` and `v`.
+pub struct Quad<M: Material> {
+    corner: VecR3,
+    u: VecR3,
+    v: VecR3,
+    /// Unit normal in the direction of `u.cross(v)`
+    normal: VecR3,
+    /// `u.cross(v) / |u.cross(v)|^2`; used to find the texture coordinates of a point.
+    w: VecR3,
+    area: f64,
+    material: M,
+}
+
+impl<M: Material> Quad<M> {
+    /// Create a new parallelogram
+    ///
+    /// # Arguments
+    /// * `corner` - One corner of the parallelogram.
+    /// * `u` - The edge from `corner` to the adjacent corner along the u texture axis.
+    /// * `v` - The edge from `corner` to the adjacent corner along the v texture axis.
+    /// * `material` - The surface material.
+    pub fn new(corner: VecR3, u: VecR3, v: VecR3, material: M) -> Self {
+        let n = u.cross(v);
+        let area = n.norm();
+        Self {
+            corner,
+            u,
+            v,
+            normal: n / area,
+            w: n / (area * area),
+            area,
+            material,
+        }
+    }
+
+    /// Intersect a ray with the parallelogram.
+    ///
+    /// # Returns
+    /// The ray position and texture coordinates of the intersection.
+    fn hit(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+        let denominator = self.normal.dot(ray.direction);
+        // The ray is parallel to the plane
+        if denominator.abs() <= 1e-12 * ray.direction.norm() {
+            return None;
+        }
+        let t = self.normal.dot(self.corner - ray.origin) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let offset = ray.at(t) - self.corner;
+        let alpha = self.w.dot(offset.cross(self.v));
+        let beta = self.w.dot(self.u.cross(offset));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some((t, alpha, beta))
+    }
+}
+
+impl<M: Material> Surface for Quad<M> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let (t, alpha, beta) = self.hit(ray, t_min, t_max)?;
+        let intersection =
+            Intersection::new(ray, t, ray.at(t), self.normal, &self.material).with_uv(alpha, beta);
+        if self.is_light() {
+            Some(intersection.with_light(self))
+        } else {
+            Some(intersection)
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let far = self.corner + self.u + self.v;
+        Some(
+            Aabb::from_points(self.corner, far)
+                .grow(self.corner + self.u)
+                .grow(self.corner + self.v),
+        )
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Samples points uniformly by area.
+    fn sample(&self, origin: &VecR3, u: (f64, f64)) -> Option<LightSample> {
+        let point = self.corner + self.u * u.0 + self.v * u.1;
+        sample_flat_light(origin, point, self.normal, self.area, &self.material)
+    }
+
+    fn pdf(&self, origin: &VecR3, direction: &VecR3) -> f64 {
+        let ray = RayR3::new(*origin, direction.as_unit());
+        match self.hit(&ray, 0.0, f64::INFINITY) {
+            Some((t, _, _)) => flat_light_pdf(t, ray.direction.dot(self.normal), self.area),
+            None => 0.0,
+        }
+    }
+}
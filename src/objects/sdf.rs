@@ -0,0 +1,202 @@
+use crate::aabb::Aabb;
+use crate::materials::Material;
+use crate::ray::RayR3;
+use crate::sdf::DistanceFunction;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::{Vec3, VecR3};
+
+/// The surface where a signed distance function is zero
+///
+/// Rays are intersected by sphere tracing: stepping along the ray by the distance to the
+/// surface until within `epsilon` of it.
+/// Normals are the gradient of the distance, estimated by finite differences.
+/// The outside of the shape, where the distance is positive, is the front face.
+pub struct Sdf<D: DistanceFunction, M: Material> {
+    shape: D,
+    material: M,
+    /// Distance from the surface at which a ray is considered to hit it
+    epsilon: f64,
+    max_steps: u32,
+    bounds: Option<Aabb>,
+}
+
+impl<D: DistanceFunction, M: Material> Sdf<D, M> {
+    /// Create a new signed distance field surface
+    ///
+    /// # Arguments
+    /// * `shape` - The signed distance function.
+    /// * `material` - The surface material.
+    pub fn new(shape: D, material: M) -> Self {
+        let bounds = shape.bounding_box();
+        Self {
+            shape,
+            material,
+            epsilon: 1e-4,
+            max_steps: 256,
+            bounds,
+        }
+    }
+
+    /// Set the distance from the surface at which a ray is considered to hit it.
+    ///
+    /// Smaller values resolve finer detail but take more steps.
+    pub fn with_epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// Set the maximum number of steps along a ray before it is considered to miss.
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Limit the surface to a box.
+    ///
+    /// Rays are only traced inside the box, which bounds unbounded shapes such as repetitions
+    /// and saves steps for shapes with loose bounds.
+    pub fn with_bounds(mut self, bounds: Aabb) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// The outward unit normal at `p`; the normalized gradient of the distance.
+    fn normal(&self, p: VecR3) -> VecR3 {
+        // Differences along the four vertices of a tetrahedron
+        let h = self.epsilon;
+        let gradient = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ]
+        .iter()
+        .fold(Vec3::new(0.0, 0.0, 0.0), |sum, &k| {
+            sum + k * self.shape.distance(p + k * h)
+        });
+        if gradient.norm_squared() > 0.0 {
+            gradient.as_unit()
+        } else {
+            // At a singular point of the field, such as the center of a sphere
+            Vec3::new(0.0, 1.0, 0.0)
+        }
+    }
+}
+
+impl<D: DistanceFunction, M: Material> Surface for Sdf<D, M> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let (mut t, t_max) = match &self.bounds {
+            Some(bounds) => {
+                let inv_direction = ray.direction.map(|x| 1.0 / x);
+                bounds.clip(ray, &inv_direction, t_min, t_max)?
+            }
+            None => (t_min, t_max),
+        };
+        // Distances are along the ray, which may not have a unit direction
+        let speed = ray.direction.norm();
+
+        // Which side of the surface the ray starts on; 1 outside and -1 inside.
+        // A ray that enters the bounds comes from outside the shape, even if the bounds touch
+        // the surface. Otherwise the side is unknown while the ray is within epsilon of the
+        // surface it is leaving.
+        let mut side = if t > t_min { Some(1.0) } else { None };
+        for _ in 0..self.max_steps {
+            let distance = self.shape.distance(ray.at(t));
+            if side.is_none() && distance.abs() >= self.epsilon {
+                side = Some(distance.signum());
+            }
+            let step = match side {
+                Some(sign) => {
+                    let distance = sign * distance;
+                    if distance < self.epsilon {
+                        let point = ray.at(t);
+                        let normal = self.normal(point);
+                        return Some(Intersection::new(ray, t, point, normal, &self.material));
+                    }
+                    distance
+                }
+                None => self.epsilon,
+            };
+            t += step / speed;
+            if t > t_max {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::materials::Lambertian;
+    use crate::sdf::{self, DistanceFunction};
+
+    fn surface<D: DistanceFunction>(shape: D) -> Sdf<D, Lambertian> {
+        Sdf::new(shape, Lambertian::new(Colour::new(0.5, 0.5, 0.5)))
+    }
+
+    fn assert_hit(surface: &dyn Surface, ray: &RayR3, t: f64, front_face: bool) {
+        let hit = surface
+            .intersect(ray, 0.001, f64::INFINITY)
+            .expect("ray should hit the surface");
+        assert!(
+            (hit.t - t).abs() < 1e-3,
+            "hit at t = {}, expected {}",
+            hit.t,
+            t
+        );
+        assert_eq!(hit.front_face, front_face);
+    }
+
+    #[test]
+    fn box_from_outside() {
+        let cube = surface(sdf::Cuboid::new(Vec3::new(1.0, 1.0, 1.0)));
+        let ray = RayR3::new(Vec3::new(0.3, 0.2, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_hit(&cube, &ray, 4.0, true);
+    }
+
+    #[test]
+    fn rounded_box_from_outside() {
+        let cube = surface(sdf::Cuboid::new(Vec3::new(1.0, 1.0, 1.0)).round(0.1));
+        let ray = RayR3::new(Vec3::new(0.3, 0.2, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_hit(&cube, &ray, 3.9, true);
+    }
+
+    #[test]
+    fn box_from_inside() {
+        let cube = surface(sdf::Cuboid::new(Vec3::new(1.0, 1.0, 1.0)));
+        let ray = RayR3::new(Vec3::new(0.3, 0.2, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_hit(&cube, &ray, 1.0, false);
+    }
+
+    #[test]
+    fn sphere_along_axis() {
+        let sphere = surface(sdf::Sphere::new(1.0));
+        let ray = RayR3::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -2.0));
+        assert_hit(&sphere, &ray, 2.0, true);
+    }
+
+    #[test]
+    fn sphere_from_inside() {
+        let sphere = surface(sdf::Sphere::new(1.0).translate(Vec3::new(1.0, 0.0, 0.0)));
+        let ray = RayR3::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_hit(&sphere, &ray, 1.0, false);
+        let hit = sphere
+            .intersect(&ray, 0.001, f64::INFINITY)
+            .expect("ray should hit the surface");
+        assert!((hit.normal - Vec3::new(0.0, -1.0, 0.0)).norm() < 1e-3);
+    }
+
+    #[test]
+    fn sphere_miss() {
+        let sphere = surface(sdf::Sphere::new(1.0));
+        let ray = RayR3::new(Vec3::new(0.0, 1.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(sphere.intersect(&ray, 0.001, f64::INFINITY).is_none());
+    }
+}
@@ -1,7 +1,11 @@
+use crate::aabb::Aabb;
+use crate::lights::LightSample;
 use crate::materials::Material;
 use crate::ray::Ray;
+use crate::sampling::orthonormal_basis;
 use crate::surface::{Intersection, Surface};
-use crate::vec3::VecR3;
+use crate::vec3::{Vec3, VecR3};
+use std::f64::consts::PI;
 
 /// A Sphere
 pub struct Sphere<M: Material> {
@@ -18,10 +22,24 @@ impl<M: Material> Sphere<M> {
             material,
         }
     }
+
+    /// One minus the cosine of the half-angle of the cone subtended by the sphere.
+    ///
+    /// Returns None if the point at `distance_squared` from the center is inside the sphere.
+    fn one_minus_cos_max(&self, distance_squared: f64) -> Option<f64> {
+        let r_squared = self.radius * self.radius;
+        if distance_squared <= r_squared {
+            return None;
+        }
+        let sin_squared_max = r_squared / distance_squared;
+        let cos_max = (1.0 - sin_squared_max).sqrt();
+        // Equal to 1 - cos_max but accurate for small, distant spheres
+        Some(sin_squared_max / (1.0 + cos_max))
+    }
 }
 
 impl<M: Material> Surface for Sphere<M> {
-    fn intersect(&self, ray: &Ray<f64>, t_min: f64, t_max: f64) -> Option<Intersection> {
+    fn intersect(&self, ray: &Ray<f64>, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
         let rel_origin = ray.origin - self.center;
         let a = ray.direction.norm_squared();
         let half_b = ray.direction.dot(rel_origin);
@@ -45,12 +63,79 @@ impl<M: Material> Surface for Sphere<M> {
 
         let point = ray.at(t);
         let outward_normal = (point - self.center) / self.radius;
-        return Some(Intersection::new(
-            ray,
-            t,
-            point,
-            outward_normal,
-            &self.material,
-        ));
+        let (u, v) = sphere_uv(outward_normal);
+        let intersection =
+            Intersection::new(ray, t, point, outward_normal, &self.material).with_uv(u, v);
+        if self.is_light() {
+            Some(intersection.with_light(self))
+        } else {
+            Some(intersection)
+        }
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        let offset = Vec3::new(r, r, r);
+        Some(Aabb::new(self.center - offset, self.center + offset))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Samples directions uniformly within the cone of directions that hit the sphere.
+    fn sample(&self, origin: &VecR3, u: (f64, f64)) -> Option<LightSample> {
+        let to_center = self.center - *origin;
+        let distance_squared = to_center.norm_squared();
+        let one_minus_cos_max = self.one_minus_cos_max(distance_squared)?;
+        let distance_to_center = distance_squared.sqrt();
+
+        let cos_theta = 1.0 - u.0 * one_minus_cos_max;
+        let sin_theta_squared = (1.0 - cos_theta * cos_theta).max(0.0);
+        let sin_theta = sin_theta_squared.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let w = to_center / distance_to_center;
+        let (a, b) = orthonormal_basis(&w);
+        let direction = a * (sin_theta * phi.cos()) + b * (sin_theta * phi.sin()) + w * cos_theta;
+
+        // Nearest intersection of the sampled direction with the sphere
+        let r_squared = self.radius * self.radius;
+        let distance = distance_to_center * cos_theta
+            - (r_squared - distance_squared * sin_theta_squared)
+                .max(0.0)
+                .sqrt();
+        let point = *origin + direction * distance;
+        let outward_normal = (point - self.center) / self.radius;
+        let front_face = direction.dot(outward_normal) < 0.0;
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.material.emitted(&point, front_face),
+            pdf: 1.0 / (2.0 * PI * one_minus_cos_max),
+        })
+    }
+
+    fn pdf(&self, origin: &VecR3, direction: &VecR3) -> f64 {
+        let to_center = self.center - *origin;
+        let distance_squared = to_center.norm_squared();
+        let one_minus_cos_max = match self.one_minus_cos_max(distance_squared) {
+            Some(x) => x,
+            None => return 0.0,
+        };
+        let cos_theta = direction.dot(to_center) / (direction.norm() * distance_squared.sqrt());
+        if cos_theta < 1.0 - one_minus_cos_max {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * one_minus_cos_max)
+    }
+}
+
+/// Texture coordinates of a point on the unit sphere.
+///
+/// u in [0, 1] is the angle around the Y axis starting from -X.
+/// v in [0, 1] is the angle from -Y to +Y.
+fn sphere_uv(point: VecR3) -> (f64, f64) {
+    let theta = (-point.y).acos();
+    let phi = (-point.z).atan2(point.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
 }
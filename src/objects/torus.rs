@@ -0,0 +1,178 @@
+use crate::aabb::Aabb;
+use crate::materials::Material;
+use crate::objects::frame::{sweep_angle, Frame, LocalHit};
+use crate::ray::RayR3;
+use crate::roots;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::{Vec3, VecR3};
+use std::f64::consts::PI;
+
+/// A ring torus
+///
+/// The surface swept by a circle of radius `minor_radius` whose center moves
+/// around a circle of radius `major_radius`.
+/// Texture coordinates are the angles around the axis and around the tube as fractions of a turn.
+pub struct Torus<M: Material> {
+    /// Frame with its origin at the center and z along the axis
+    frame: Frame,
+    major_radius: f64,
+    minor_radius: f64,
+    material: M,
+}
+
+impl<M: Material> Torus<M> {
+    /// Create a new torus
+    ///
+    /// # Arguments
+    /// * `center` - Center of the hole.
+    /// * `axis` - Direction of the axis through the hole.
+    /// * `major_radius` - Distance from the center to the middle of the tube.
+    /// * `minor_radius` - Radius of the tube.
+    /// * `material` - The surface material.
+    pub fn new(
+        center: VecR3,
+        axis: VecR3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: M,
+    ) -> Self {
+        Self {
+            frame: Frame::new(center, axis),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    /// Intersect a ray in local coordinates.
+    fn hit_local(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<LocalHit> {
+        let big_r = self.major_radius;
+        let r = self.minor_radius;
+
+        // Solve with a unit direction and an origin moved close to the torus,
+        // which keeps the coefficients of the quartic well scaled.
+        let scale = ray.direction.norm();
+        let d = ray.direction / scale;
+        let bound = big_r + r;
+        let shift = (-ray.origin.dot(d) - bound).max(0.0);
+        let o = ray.origin + d * shift;
+
+        // |p|^2 + R^2 - r^2 = 4 R^2 (x^2 + y^2) with p = o + s d
+        let e = o.dot(d);
+        let f = o.norm_squared() + big_r * big_r - r * r;
+        let four_r2 = 4.0 * big_r * big_r;
+        let a = 4.0 * e;
+        let b = 4.0 * e * e + 2.0 * f - four_r2 * (d.x * d.x + d.y * d.y);
+        let c = 4.0 * e * f - 2.0 * four_r2 * (o.x * d.x + o.y * d.y);
+        let c0 = f * f - four_r2 * (o.x * o.x + o.y * o.y);
+
+        let t = roots::quartic(a, b, c, c0)
+            .into_iter()
+            .map(|s| (s + shift) / scale)
+            .find(|&t| t >= t_min && t <= t_max)?;
+
+        let p = ray.at(t);
+        let ring = Vec3::new(p.x, p.y, 0.0);
+        let ring_norm = ring.norm();
+        let tube_center = if ring_norm > 0.0 {
+            ring * (big_r / ring_norm)
+        } else {
+            Vec3::new(big_r, 0.0, 0.0)
+        };
+        let normal = (p - tube_center).as_unit();
+        // Angle around the tube, measured from the outside of the ring
+        let outward = normal.dot(tube_center) / big_r;
+        let tube_angle = normal.z.atan2(outward).rem_euclid(2.0 * PI);
+        Some(LocalHit {
+            t,
+            normal,
+            uv: (sweep_angle(p) / (2.0 * PI), tube_angle / (2.0 * PI)),
+        })
+    }
+}
+
+impl<M: Material> Surface for Torus<M> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let hit = self.hit_local(&self.frame.ray_to_local(ray), t_min, t_max)?;
+        Some(hit.to_world(&self.frame, ray, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let outer = self.major_radius + self.minor_radius;
+        let r = self.minor_radius;
+        let local = Aabb::new(Vec3::new(-outer, -outer, -r), Vec3::new(outer, outer, r));
+        Some(self.frame.bounding_box(&local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::materials::Lambertian;
+
+    fn torus() -> Torus<Lambertian> {
+        Torus::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            Lambertian::new(Colour::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    fn hit(surface: &dyn Surface, origin: VecR3, direction: VecR3) -> Option<Intersection<'_>> {
+        surface.intersect(&RayR3::new(origin, direction), 0.001, f64::INFINITY)
+    }
+
+    #[test]
+    fn outer_hit() {
+        let torus = torus();
+        let hit = hit(&torus, Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((hit.t - 7.5).abs() < 1e-9);
+        assert!(hit.front_face);
+        assert!((hit.normal - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn axial_rays() {
+        let torus = torus();
+        // Through the hole
+        assert!(hit(&torus, Vec3::new(0.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).is_none());
+        // Onto the top of the tube
+        let top = hit(&torus, Vec3::new(2.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!((top.t - 9.5).abs() < 1e-9);
+        assert!((top.normal - Vec3::new(0.0, 1.0, 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn hit_from_inside_tube() {
+        let torus = torus();
+        let hit = hit(&torus, Vec3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((hit.t - 0.5).abs() < 1e-9);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn grazing_rays() {
+        let torus = torus();
+        // Just below the top of the tube
+        let y: f64 = 0.49;
+        let hit_ray = hit(&torus, Vec3::new(-10.0, y, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        let expected = 10.0 - 2.0 - (0.25 - y * y).sqrt();
+        assert!((hit_ray.t - expected).abs() < 1e-6);
+        assert!(hit(
+            &torus,
+            Vec3::new(-10.0, 0.51, 0.0),
+            Vec3::new(1.0, 0.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn distant_ray_with_scaled_direction() {
+        let torus = torus();
+        let hit = hit(&torus, Vec3::new(0.0, 0.0, 1e4), Vec3::new(0.0, 0.0, -4.0)).unwrap();
+        assert!((hit.t - (1e4 - 2.5) / 4.0).abs() < 1e-6);
+    }
+}
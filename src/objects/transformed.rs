@@ -0,0 +1,214 @@
+use crate::aabb::Aabb;
+use crate::lights::{address, LightSample};
+use crate::ray::RayR3;
+use crate::surface::{Intersection, Surface};
+use crate::transform::Transform;
+use crate::vec3::VecR3;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A surface placed in the scene by an affine transform
+///
+/// Rays are transformed into the space of the surface and intersections are transformed
+/// back out. The surface may be an `Arc<dyn Surface>` so that the same geometry can be
+/// placed many times without copying it.
+///
+/// If the surface is itself a light it is sampled as a light by the transformed surface.
+/// Each light contained in a composite surface is sampled through its own transformed copy,
+/// which shares the light with the composite surface.
+pub struct Transformed<S: Surface> {
+    surface: S,
+    to_world: Transform,
+    to_object: Transform,
+    /// The lights of a composite surface, each placed by the same transform
+    lights: Vec<Arc<dyn Surface>>,
+    /// Index into `lights` by the address of the light in object space
+    light_indices: HashMap<usize, usize>,
+    /// Absolute determinant of the linear part of `to_object`
+    ///
+    /// The ratio of object space to world space volumes,
+    /// used to convert the density of light samples between the spaces.
+    inverse_determinant: f64,
+}
+
+impl<S: Surface> Transformed<S> {
+    /// Create a transformed surface
+    ///
+    /// # Arguments
+    /// * `surface` - The surface in object space.
+    /// * `transform` - Maps object space to world space.
+    pub fn new(surface: S, transform: Transform) -> Self {
+        let to_object = transform.inverse();
+        let object_lights = if surface.is_light() {
+            Vec::new()
+        } else {
+            surface.lights()
+        };
+        let light_indices = object_lights
+            .iter()
+            .enumerate()
+            .map(|(i, light)| (address(&**light), i))
+            .collect();
+        let lights = object_lights
+            .into_iter()
+            .map(|light| Arc::new(Transformed::new(light, transform)) as Arc<dyn Surface>)
+            .collect();
+        Self {
+            surface,
+            to_world: transform,
+            to_object,
+            lights,
+            light_indices,
+            inverse_determinant: to_object.matrix().linear_determinant().abs(),
+        }
+    }
+
+    /// The surface in object space
+    pub fn surface(&self) -> &S {
+        &self.surface
+    }
+
+    /// The transform from object space to world space
+    pub fn transform(&self) -> &Transform {
+        &self.to_world
+    }
+}
+
+impl<S: Surface> Surface for Transformed<S> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let object_ray = self.to_object.ray(ray);
+        let mut intersection = self.surface.intersect(&object_ray, t_min, t_max)?;
+        intersection.point = ray.at(intersection.t);
+        // The transformed normal stays on the same side of the ray
+        intersection.normal = self.to_world.normal(intersection.normal).as_unit();
+        intersection.shading_normal = self.to_world.normal(intersection.shading_normal).as_unit();
+        if let Some(light) = intersection.light {
+            intersection.light = if self.is_light() {
+                Some(self)
+            } else {
+                self.light_indices
+                    .get(&address(light))
+                    .map(|&i| &*self.lights[i])
+            };
+        }
+        Some(intersection)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.surface
+            .bounding_box()
+            .map(|aabb| self.to_world.bounding_box(&aabb))
+    }
+
+    fn is_light(&self) -> bool {
+        self.surface.is_light()
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Surface>> {
+        self.lights.clone()
+    }
+
+    fn sample(&self, origin: &VecR3, u: (f64, f64)) -> Option<LightSample> {
+        let sample = self.surface.sample(&self.to_object.point(*origin), u)?;
+        let direction = self.to_world.vector(sample.direction);
+        let scale = direction.norm();
+        Some(LightSample {
+            direction: direction / scale,
+            distance: sample.distance * scale,
+            radiance: sample.radiance,
+            // A unit direction w in world space maps to L w in object space, where L is the
+            // linear part of `to_object`; solid angles change by |det L| / |L w|^3.
+            pdf: sample.pdf * self.inverse_determinant * scale * scale * scale,
+        })
+    }
+
+    fn pdf(&self, origin: &VecR3, direction: &VecR3) -> f64 {
+        let unit = direction.as_unit();
+        let object_direction = self.to_object.vector(unit);
+        let object_pdf = self
+            .surface
+            .pdf(&self.to_object.point(*origin), &object_direction);
+        object_pdf * self.inverse_determinant / object_direction.norm().powi(3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::materials::DiffuseLight;
+    use crate::objects::{Quad, Sphere};
+    use crate::sampling::{uniform_sphere, uniform_sphere_pdf, SampleRng};
+    use crate::vec3::Vec3;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn composite_lights_are_transformed() {
+        // A unit square light facing down from z = 0, moved up to z = 2
+        let light: Arc<dyn Surface> = Arc::new(Quad::new(
+            Vec3::new(-0.5, 0.5, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            DiffuseLight::new(Colour::new(1.0, 1.0, 1.0)),
+        ));
+        let group: Vec<Arc<dyn Surface>> = vec![light];
+        let transformed = Transformed::new(group, Transform::translation(Vec3::new(0.0, 0.0, 2.0)));
+        assert!(!transformed.is_light());
+        let lights = transformed.lights();
+        assert_eq!(lights.len(), 1);
+
+        // Hits report the transformed light, which samples the light where it is placed
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let ray = RayR3::new(origin, Vec3::new(0.0, 0.0, 1.0));
+        let hit = transformed.intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(address(hit.light.unwrap()), address(&*lights[0]));
+        let sample = lights[0].sample(&origin, (0.5, 0.5)).unwrap();
+        assert!((sample.direction - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-9);
+        assert!((sample.distance - 2.0).abs() < 1e-9);
+        let pdf = lights[0].pdf(&origin, &ray.direction);
+        assert!((sample.pdf - pdf).abs() < 1e-9 && pdf > 0.0);
+    }
+
+    #[test]
+    fn sample_and_pdf_agree_under_scaling() {
+        // A unit sphere light stretched into an ellipsoid in front of the origin
+        let sphere = Sphere::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            DiffuseLight::new(Colour::new(1.0, 1.0, 1.0)),
+        );
+        let transform = Transform::scaling(Vec3::new(2.0, 0.5, 1.5))
+            .then(&Transform::rotation(Vec3::new(1.0, 1.0, 0.0), 30.0))
+            .then(&Transform::translation(Vec3::new(0.5, 0.0, 4.0)));
+        let ellipsoid = Transformed::new(sphere, transform);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+
+        let mut rng = SampleRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let u = (rng.gen(), rng.gen());
+            let sample = ellipsoid.sample(&origin, u).unwrap();
+            let pdf = ellipsoid.pdf(&origin, &sample.direction);
+            assert!(
+                (sample.pdf - pdf).abs() < 1e-9 * pdf,
+                "{} != {}",
+                sample.pdf,
+                pdf
+            );
+            let hit = ellipsoid
+                .intersect(&RayR3::new(origin, sample.direction), 0.001, f64::INFINITY)
+                .unwrap();
+            assert!(hit.t <= sample.distance + 1e-9);
+        }
+
+        // The pdf integrates to one over all directions
+        let n = 200_000;
+        let integral = (0..n)
+            .map(|_| {
+                let direction = uniform_sphere((rng.gen(), rng.gen()));
+                ellipsoid.pdf(&origin, &direction) / uniform_sphere_pdf()
+            })
+            .sum::<f64>()
+            / n as f64;
+        assert!((integral - 1.0).abs() < 0.03, "{}", integral);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::aabb::Aabb;
+use crate::lights::{flat_light_pdf, sample_flat_light, LightSample};
+use crate::materials::Material;
+use crate::ray::RayR3;
+use crate::surface::{Intersection, Surface};
+use crate::vec3::VecR3;
+
+/// A Triangle
+///
+/// The front face is the side from which the vertices appear counter-clockwise.
+pub struct Triangle<M: Material> {
+    vertices: [VecR3; 3],
+    material: M,
+}
+
+impl<M: Material> Triangle<M> {
+    pub fn new(a: VecR3, b: VecR3, c: VecR3, material: M) -> Self {
+        Self {
+            vertices: [a, b, c],
+            material,
+        }
+    }
+
+    /// The unit normal pointing from the front face, and the area.
+    fn normal_and_area(&self) -> (VecR3, f64) {
+        let [a, b, c] = self.vertices;
+        let cross = (b - a).cross(c - a);
+        let norm = cross.norm();
+        (cross / norm, 0.5 * norm)
+    }
+}
+
+impl<M: Material> Surface for Triangle<M> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        let [a, b, c] = self.vertices;
+        let (t, b1, b2) = intersect_triangle(ray, a, b, c, t_min, t_max)?;
+        let front_normal = (b - a).cross(c - a).as_unit();
+        let intersection =
+            Intersection::new(ray, t, ray.at(t), front_normal, &self.material).with_uv(b1, b2);
+        if self.is_light() {
+            Some(intersection.with_light(self))
+        } else {
+            Some(intersection)
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [a, b, c] = self.vertices;
+        Some(Aabb::from_points(a, b).grow(c))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Samples points uniformly by area.
+    fn sample(&self, origin: &VecR3, u: (f64, f64)) -> Option<LightSample> {
+        let [a, b, c] = self.vertices;
+        let sqrt_u0 = u.0.sqrt();
+        let (b1, b2) = (1.0 - sqrt_u0, u.1 * sqrt_u0);
+        let point = a + (b - a) * b1 + (c - a) * b2;
+        let (normal, area) = self.normal_and_area();
+        sample_flat_light(origin, point, normal, area, &self.material)
+    }
+
+    fn pdf(&self, origin: &VecR3, direction: &VecR3) -> f64 {
+        let [a, b, c] = self.vertices;
+        let ray = RayR3::new(*origin, direction.as_unit());
+        match intersect_triangle(&ray, a, b, c, 0.0, f64::INFINITY) {
+            Some((t, _, _)) => {
+                let (normal, area) = self.normal_and_area();
+                flat_light_pdf(t, ray.direction.dot(normal), area)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// Intersect a ray with a triangle using the Möller–Trumbore algorithm.
+///
+/// # Returns
+/// The ray position `t` of the intersection and the barycentric coordinates `(b1, b2)`
+/// of the intersection point relative to vertices `b` and `c`.
+/// Returns None if there is no intersection between t_min and t_max.
+pub(crate) fn intersect_triangle(
+    ray: &RayR3,
+    a: VecR3,
+    b: VecR3,
+    c: VecR3,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = ray.direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    // Scale the parallel-ray tolerance with the size of the problem
+    // so that both tiny and huge triangles are handled.
+    let scale = edge1.norm() * edge2.norm() * ray.direction.norm();
+    if determinant.abs() <= 1e-12 * scale {
+        return None;
+    }
+    let inv_determinant = determinant.recip();
+
+    let s = ray.origin - a;
+    let b1 = s.dot(p) * inv_determinant;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let b2 = ray.direction.dot(q) * inv_determinant;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inv_determinant;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::materials::DiffuseLight;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn sample_matches_pdf() {
+        // Front face towards +z
+        let light = Triangle::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            DiffuseLight::new(Colour::new(1.0, 1.0, 1.0)),
+        );
+        let origin = Vec3::new(0.5, 0.5, 3.0);
+        let sample = light.sample(&origin, (0.3, 0.6)).unwrap();
+        assert_eq!(sample.radiance, Colour::new(1.0, 1.0, 1.0));
+        let pdf = light.pdf(&origin, &sample.direction);
+        assert!((sample.pdf - pdf).abs() < 1e-9 * pdf);
+
+        // The back face does not emit
+        let behind = Vec3::new(0.5, 0.5, -3.0);
+        let sample = light.sample(&behind, (0.3, 0.6)).unwrap();
+        assert_eq!(sample.radiance, Colour::new(0.0, 0.0, 0.0));
+        // Directions that miss the light have zero density
+        assert_eq!(light.pdf(&behind, &Vec3::new(1.0, 0.0, 0.0)), 0.0);
+    }
+}
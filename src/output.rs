@@ -0,0 +1,154 @@
+use crate::colour::Colour;
+use crate::loaders::float_image::lowercase_extension;
+use anyhow::{anyhow, Context};
+use exr::prelude::write_rgb_file;
+use image::codecs::hdr::HdrEncoder;
+use image::Rgb;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Whether a path names a floating-point image format supported by `save_float_image`.
+pub fn is_float_image_path<P: AsRef<Path>>(path: P) -> bool {
+    matches!(
+        lowercase_extension(path.as_ref()).as_deref(),
+        Some("exr") | Some("hdr") | Some("pfm")
+    )
+}
+
+/// Save a high dynamic range image without clipping or quantizing its values.
+///
+/// Supports OpenEXR (`.exr`), Radiance RGBE (`.hdr`) and Portable Float Map (`.pfm`)
+/// files, chosen by file extension.
+///
+/// # Arguments
+/// * `path` - The output file.
+/// * `width` - Image width in pixels.
+/// * `height` - Image height in pixels.
+/// * `pixels` - Row-major linear RGB pixels starting from the top left.
+pub fn save_float_image<P: AsRef<Path>>(
+    path: P,
+    width: usize,
+    height: usize,
+    pixels: &[Colour],
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    assert_eq!(pixels.len(), width * height);
+    match lowercase_extension(path).as_deref() {
+        Some("exr") => save_exr(path, width, height, pixels),
+        Some("hdr") => save_hdr(path, width, height, pixels),
+        Some("pfm") => save_pfm(path, width, height, pixels),
+        _ => Err(anyhow!(
+            "unsupported image format '{}'; expected .exr, .hdr or .pfm",
+            path.display()
+        )),
+    }
+    .with_context(|| format!("failed to save image '{}'", path.display()))
+}
+
+fn save_exr(path: &Path, width: usize, height: usize, pixels: &[Colour]) -> anyhow::Result<()> {
+    write_rgb_file(path, width, height, |x, y| {
+        let c = pixels[y * width + x];
+        (c.x as f32, c.y as f32, c.z as f32)
+    })?;
+    Ok(())
+}
+
+fn save_hdr(path: &Path, width: usize, height: usize, pixels: &[Colour]) -> anyhow::Result<()> {
+    let data: Vec<Rgb<f32>> = pixels
+        .iter()
+        .map(|c| Rgb([c.x as f32, c.y as f32, c.z as f32]))
+        .collect();
+    HdrEncoder::new(BufWriter::new(File::create(path)?)).encode(&data, width, height)?;
+    Ok(())
+}
+
+/// Save a colour Portable Float Map.
+///
+/// Rows are stored from the bottom up as little-endian 32-bit floats.
+fn save_pfm(path: &Path, width: usize, height: usize, pixels: &[Colour]) -> anyhow::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    // A negative scale indicates little-endian data
+    write!(file, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in pixels.chunks(width).rev() {
+        for c in row {
+            for v in [c.x, c.y, c.z].iter() {
+                file.write_all(&(*v as f32).to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaders::load_float_image;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("raytracer-output-{}-{}", std::process::id(), name))
+    }
+
+    /// A 3 x 2 image with a different value in every channel
+    fn pixels() -> Vec<Colour> {
+        (0..6)
+            .map(|i| {
+                let v = i as f64;
+                Colour::new(v, v + 0.25, v * 100.0 + 0.5)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pfm_layout() {
+        let path = temp_path("image.PFM");
+        save_float_image(&path, 3, 2, &pixels()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let values: Vec<f32> = bytes[header.len()..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        // The bottom row comes first
+        let expected: Vec<f32> = pixels()[3..]
+            .iter()
+            .chain(&pixels()[..3])
+            .flat_map(|c| [c.x as f32, c.y as f32, c.z as f32])
+            .collect();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn exr_and_hdr_round_trip() {
+        for (name, tolerance) in [("image.exr", 1e-6), ("image.Hdr", 1e-2)] {
+            let path = temp_path(name);
+            save_float_image(&path, 3, 2, &pixels()).unwrap();
+            let image = load_float_image(&path).unwrap();
+            assert_eq!((image.width, image.height), (3, 2));
+            for (loaded, saved) in image.pixels.iter().zip(pixels()) {
+                // RGBE shares one exponent between the channels
+                let scale = saved.x.max(saved.y).max(saved.z).max(1.0);
+                assert!(
+                    (*loaded - saved).norm() < tolerance * scale,
+                    "{}: {:?}",
+                    name,
+                    loaded
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn formats_are_chosen_by_extension() {
+        assert!(is_float_image_path("a.exr"));
+        assert!(is_float_image_path("b.HDR"));
+        assert!(is_float_image_path("c.Pfm"));
+        assert!(!is_float_image_path("d.png"));
+        assert!(!is_float_image_path("pfm"));
+        let error = save_float_image(temp_path("image.png"), 3, 2, &pixels()).unwrap_err();
+        assert!(format!("{:#}", error).contains("expected .exr, .hdr or .pfm"));
+    }
+}
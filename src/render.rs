@@ -0,0 +1,1007 @@
+use crate::camera::Camera;
+use crate::checkpoint::{hash_bytes, Checkpoint};
+use crate::colour::{luminance, Colour};
+use crate::environments::Environment;
+use crate::film::{Film, FilmTile, Filter};
+use crate::lights::LightList;
+use crate::output::{is_float_image_path, save_float_image};
+use crate::ray::RayR3;
+use crate::samplers::{Sampler, SamplerKind};
+use crate::sampling::{balance_heuristic, power_heuristic};
+use crate::surface::{Intersection, Surface};
+use crate::threadpool::{ThreadPool, WorkerPanic};
+use crate::tiles::{tiles, Tile, TileOrder};
+use crate::tone_mapping::ToneMapping;
+use anyhow::{anyhow, Context};
+use image::RgbImage;
+use std::error::Error;
+use std::f64;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A light transport algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integrator {
+    /// Light is only found when scattered rays happen to hit a light source.
+    Path,
+    /// Next-event estimation: light sources are also sampled directly at each bounce.
+    Nee,
+    /// Next-event estimation and scattered rays that hit light sources,
+    /// combined with multiple importance sampling.
+    Mis,
+}
+
+impl FromStr for Integrator {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(Integrator::Path),
+            "nee" => Ok(Integrator::Nee),
+            "mis" => Ok(Integrator::Mis),
+            _ => Err(anyhow!(
+                "unknown integrator '{}'; expected path, nee or mis",
+                s
+            )),
+        }
+    }
+}
+
+/// A multiple importance sampling heuristic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MisHeuristic {
+    Balance,
+    Power,
+}
+
+impl MisHeuristic {
+    /// Weight of a sample generated with density `pdf` by one of two strategies.
+    pub fn weight(self, pdf: f64, other_pdf: f64) -> f64 {
+        match self {
+            MisHeuristic::Balance => balance_heuristic(pdf, other_pdf),
+            MisHeuristic::Power => power_heuristic(pdf, other_pdf),
+        }
+    }
+}
+
+impl FromStr for MisHeuristic {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "balance" => Ok(MisHeuristic::Balance),
+            "power" => Ok(MisHeuristic::Power),
+            _ => Err(anyhow!(
+                "unknown heuristic '{}'; expected balance or power",
+                s
+            )),
+        }
+    }
+}
+
+/// Settings that control how an image is rendered
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    /// Image width in pixels
+    pub width: u32,
+    /// Image height in pixels
+    pub height: u32,
+    pub samples_per_pixel: u32,
+    /// Maximum number of times a ray may scatter
+    pub max_depth: u32,
+    /// Number of worker threads
+    pub num_workers: usize,
+    pub integrator: Integrator,
+    pub mis_heuristic: MisHeuristic,
+    /// Seed of the random numbers used for sampling.
+    /// Renders with the same seed and settings produce identical images.
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    /// Pixel reconstruction filter
+    pub filter: Filter,
+    /// Width and height of the tiles of pixels handed out to the workers
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    /// Stop sampling pixels early once they are no longer noisy.
+    /// If None, every pixel takes `samples_per_pixel` samples.
+    pub adaptive: Option<AdaptiveSampling>,
+}
+
+impl RenderSettings {
+    /// A hash of the settings that affect the rendered image
+    ///
+    /// Settings that only affect how the work is done, such as `num_workers`, are excluded.
+    pub fn fingerprint(&self) -> u64 {
+        let settings = RenderSettings {
+            num_workers: 0,
+            ..self.clone()
+        };
+        // The debug representation includes every field and formats floats exactly
+        hash_bytes(format!("{:?}", settings).as_bytes())
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 400,
+            height: 266,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            num_workers: 1,
+            integrator: Integrator::Mis,
+            mis_heuristic: MisHeuristic::Power,
+            seed: 0,
+            sampler: SamplerKind::Sobol,
+            filter: Filter::default(),
+            tile_size: 32,
+            tile_order: TileOrder::Scanline,
+            adaptive: None,
+        }
+    }
+}
+
+/// Settings for adaptive sampling
+///
+/// Each pixel takes at least `min_samples` samples and then continues until the relative
+/// standard error of its mean luminance is at most `noise_threshold`
+/// or it has taken `samples_per_pixel` samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub noise_threshold: f64,
+}
+
+impl AdaptiveSampling {
+    /// Whether a pixel with the given sample statistics needs no more samples
+    fn is_converged(&self, stats: &PixelStats) -> bool {
+        stats.count >= self.min_samples.max(2) && stats.relative_error() <= self.noise_threshold
+    }
+}
+
+/// Running mean and variance of the luminance of the samples of a pixel
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PixelStats {
+    /// Number of samples with a finite luminance
+    pub count: u32,
+    pub mean: f64,
+    /// Sum of squared differences from the mean
+    pub m2: f64,
+}
+
+impl PixelStats {
+    /// Luminance below which errors are measured relative to this value instead of the mean,
+    /// so that dark pixels do not need an unbounded number of samples.
+    const MIN_LUMINANCE: f64 = 0.01;
+
+    /// Add a sample using Welford's algorithm.
+    fn add(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Unbiased sample variance
+    fn variance(&self) -> f64 {
+        if self.count < 2 {
+            f64::INFINITY
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    /// Standard error of the mean relative to the mean
+    fn relative_error(&self) -> f64 {
+        (self.variance() / self.count as f64).sqrt() / self.mean.max(Self::MIN_LUMINANCE)
+    }
+}
+
+/// An image with floating-point linear RGB pixels produced by a `Renderer`
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    /// Row-major pixels starting from the top left
+    pub pixels: Vec<Colour>,
+    /// Number of samples taken within each pixel, in the same order as `pixels`
+    pub samples: Vec<u32>,
+}
+
+impl Framebuffer {
+    /// Create a black framebuffer.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Colour::new(0.0, 0.0, 0.0); (width * height) as usize],
+            samples: vec![0; (width * height) as usize],
+        }
+    }
+
+    /// The pixel at column `x` and row `y`
+    pub fn get(&self, x: u32, y: u32) -> Colour {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Set the pixel at column `x` and row `y`
+    pub fn set(&mut self, x: u32, y: u32, colour: Colour) {
+        self.pixels[(y * self.width + x) as usize] = colour;
+    }
+
+    /// A greyscale image of the number of samples taken in each pixel
+    ///
+    /// Values are scaled linearly so that the most sampled pixel is white.
+    /// Save it with `save_linear` to keep the values proportional to the sample counts.
+    pub fn sample_count_image(&self) -> Framebuffer {
+        let max = self.samples.iter().copied().max().unwrap_or(0).max(1) as f64;
+        let mut image = Framebuffer::new(self.width, self.height);
+        for (pixel, &count) in image.pixels.iter_mut().zip(&self.samples) {
+            let value = count as f64 / max;
+            *pixel = Colour::new(value, value, value);
+        }
+        image.samples = self.samples.clone();
+        image
+    }
+
+    /// Convert to an 8-bit sRGB image.
+    pub fn to_rgb_image(&self, tone_mapping: &ToneMapping) -> RgbImage {
+        self.encode(|c| tone_mapping.apply_srgb(c))
+    }
+
+    /// Convert to an 8-bit image by applying a function to each pixel.
+    fn encode<F: Fn(Colour) -> Colour>(&self, f: F) -> RgbImage {
+        let mut image = RgbImage::new(self.width, self.height);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            *pixel = f(self.get(x, y)).into();
+        }
+        image
+    }
+
+    /// Save the image, choosing the format from the file extension.
+    ///
+    /// OpenEXR (`.exr`), Radiance (`.hdr`) and PFM (`.pfm`) files store the linear values.
+    /// Other formats such as PNG and JPEG store the 8-bit image from `to_rgb_image`;
+    /// `tone_mapping` is only applied to these.
+    pub fn save<P: AsRef<Path>>(&self, path: P, tone_mapping: &ToneMapping) -> anyhow::Result<()> {
+        self.save_encoded(path.as_ref(), |c| tone_mapping.apply_srgb(c))
+    }
+
+    /// Save the image without tone mapping or sRGB encoding.
+    ///
+    /// 8-bit formats store values in [0, 1] linearly, so that a value of 0.5 is saved as 128;
+    /// floating-point formats are the same as with `save`.
+    pub fn save_linear<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        self.save_encoded(path.as_ref(), |c| c)
+    }
+
+    fn save_encoded<F: Fn(Colour) -> Colour>(&self, path: &Path, f: F) -> anyhow::Result<()> {
+        if is_float_image_path(path) {
+            return save_float_image(
+                path,
+                self.width as usize,
+                self.height as usize,
+                &self.pixels,
+            );
+        }
+        self.encode(f)
+            .save(path)
+            .with_context(|| format!("failed to save image '{}'", path.display()))
+    }
+}
+
+/// Progress of a render
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    /// Number of pixels rendered so far, counting a pixel once in each pass
+    pub completed: u64,
+    /// Total number of pixels to render in all passes
+    pub total: u64,
+    /// Index of the current pass; always 0 unless rendering progressively
+    pub pass: u32,
+}
+
+/// Requests that a render stop early
+///
+/// Clones share the same state so a token can be cancelled from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask any render using this token to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `cancel` has been called
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// The error returned when a render stops before it completes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    /// The render was cancelled with a `CancelToken`.
+    Cancelled,
+    /// A worker thread panicked.
+    WorkerPanic(WorkerPanic),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::Cancelled => write!(f, "render cancelled"),
+            RenderError::WorkerPanic(err) => write!(f, "render failed: {}", err),
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::Cancelled => None,
+            RenderError::WorkerPanic(err) => Some(err),
+        }
+    }
+}
+
+impl From<WorkerPanic> for RenderError {
+    fn from(err: WorkerPanic) -> Self {
+        RenderError::WorkerPanic(err)
+    }
+}
+
+/// Renders images of a scene
+pub struct Renderer<S> {
+    state: RenderState<S>,
+}
+
+struct RenderState<S> {
+    camera: Camera,
+    surface: S,
+    background: Box<dyn Environment>,
+    lights: LightList,
+    settings: RenderSettings,
+}
+
+impl<S: Surface> Renderer<S> {
+    /// Create a renderer
+    ///
+    /// # Arguments
+    /// * `camera` - The camera; its aspect ratio should match that of the settings.
+    /// * `surface` - The objects in the scene.
+    /// * `background` - Light arriving from directions in which no object is hit.
+    /// * `settings` - Render settings.
+    pub fn new(
+        camera: Camera,
+        surface: S,
+        background: Box<dyn Environment>,
+        settings: RenderSettings,
+    ) -> Self {
+        let lights = LightList::new(&surface, &*background);
+        Self {
+            state: RenderState {
+                camera,
+                surface,
+                background,
+                lights,
+                settings,
+            },
+        }
+    }
+
+    /// The render settings
+    pub fn settings(&self) -> &RenderSettings {
+        &self.state.settings
+    }
+
+    /// Render an image.
+    ///
+    /// Panics if a worker thread panicked.
+    pub fn render(&self) -> Framebuffer {
+        self.render_with(|_| {}, &CancelToken::new())
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Render an image, reporting progress and stopping early if cancelled.
+    ///
+    /// # Arguments
+    /// * `progress` - Called from the calling thread each time a tile is completed.
+    /// * `cancel` - Checked by the workers between pixels.
+    ///
+    /// # Returns
+    /// The rendered image, or an error if `cancel` was cancelled before the render finished
+    /// or a worker panicked.
+    pub fn render_with<F>(
+        &self,
+        mut progress: F,
+        cancel: &CancelToken,
+    ) -> Result<Framebuffer, RenderError>
+    where
+        F: FnMut(Progress),
+    {
+        let settings = &self.state.settings;
+        let film = Film::new(settings.width, settings.height, settings.filter);
+        let mut stats = self.new_stats();
+        let total = settings.width as u64 * settings.height as u64;
+        let tiles = self
+            .tiles()
+            .into_iter()
+            .map(|tile| (tile, 0..settings.samples_per_pixel))
+            .collect();
+        self.render_tiles(
+            &film,
+            &mut stats,
+            tiles,
+            &mut |_, completed| {
+                progress(Progress {
+                    completed,
+                    total,
+                    pass: 0,
+                })
+            },
+            cancel,
+        )?;
+        if cancel.is_cancelled() {
+            return Err(RenderError::Cancelled);
+        }
+        Ok(film.to_framebuffer())
+    }
+
+    /// Render an image progressively in passes that each take one sample per pixel.
+    ///
+    /// Rendering stops after `samples_per_pixel` passes, once adaptive sampling has stopped
+    /// sampling every pixel, or when cancelled.
+    ///
+    /// # Arguments
+    /// * `progress` - Called from the calling thread each time a tile is completed,
+    ///   along with the film holding every sample taken so far.
+    /// * `cancel` - Checked by the workers between pixels.
+    ///
+    /// # Returns
+    /// The image formed by all samples taken before rendering stopped,
+    /// or an error if a worker panicked.
+    pub fn render_progressive<F>(
+        &self,
+        mut progress: F,
+        cancel: &CancelToken,
+    ) -> Result<Framebuffer, WorkerPanic>
+    where
+        F: FnMut(Progress, &Film),
+    {
+        let mut render = self.start_progressive();
+        while !render.is_finished() {
+            match render.render_pass(&mut progress, cancel) {
+                Ok(()) => {}
+                Err(RenderError::Cancelled) => break,
+                Err(RenderError::WorkerPanic(err)) => return Err(err),
+            }
+        }
+        Ok(render.to_framebuffer())
+    }
+
+    /// Start a progressive render whose passes are rendered by the caller.
+    pub fn start_progressive(&self) -> ProgressiveRender<'_, S> {
+        let settings = &self.state.settings;
+        let tiles = self.tiles();
+        ProgressiveRender {
+            renderer: self,
+            film: Film::new(settings.width, settings.height, settings.filter),
+            stats: self.new_stats(),
+            tile_passes: vec![0; tiles.len()],
+            tiles,
+            converged: false,
+        }
+    }
+
+    /// Continue a progressive render from a checkpoint.
+    ///
+    /// # Arguments
+    /// * `checkpoint` - A checkpoint saved by a render of the same scene with the same settings.
+    /// * `scene_hash` - Identifies the scene; must match the hash the checkpoint was made with.
+    ///
+    /// # Returns
+    /// The render or an error if the checkpoint was made with a different scene or settings.
+    pub fn resume_progressive(
+        &self,
+        checkpoint: Checkpoint,
+        scene_hash: u64,
+    ) -> anyhow::Result<ProgressiveRender<'_, S>> {
+        let settings = &self.state.settings;
+        if (checkpoint.width, checkpoint.height) != (settings.width, settings.height) {
+            return Err(anyhow!(
+                "checkpoint is for a {} x {} image, not {} x {}",
+                checkpoint.width,
+                checkpoint.height,
+                settings.width,
+                settings.height
+            ));
+        }
+        if checkpoint.seed != settings.seed {
+            return Err(anyhow!(
+                "checkpoint was rendered with seed {}, not {}",
+                checkpoint.seed,
+                settings.seed
+            ));
+        }
+        if checkpoint.settings_hash != settings.fingerprint() {
+            return Err(anyhow!("checkpoint was rendered with different settings"));
+        }
+        if checkpoint.scene_hash != scene_hash {
+            return Err(anyhow!("checkpoint was rendered from a different scene"));
+        }
+        let tiles = self.tiles();
+        if checkpoint.tile_passes.len() != tiles.len() {
+            return Err(anyhow!("checkpoint has the wrong number of tiles"));
+        }
+        Ok(ProgressiveRender {
+            renderer: self,
+            film: Film::from_pixels(
+                settings.width,
+                settings.height,
+                settings.filter,
+                checkpoint.film,
+            ),
+            stats: checkpoint.stats,
+            tiles,
+            tile_passes: checkpoint.tile_passes,
+            converged: false,
+        })
+    }
+
+    /// Per-pixel sample statistics for a new render
+    fn new_stats(&self) -> Vec<PixelStats> {
+        let settings = &self.state.settings;
+        vec![PixelStats::default(); (settings.width * settings.height) as usize]
+    }
+
+    /// The tiles of the image in the order they are rendered
+    fn tiles(&self) -> Vec<Tile> {
+        let settings = &self.state.settings;
+        tiles(
+            settings.width,
+            settings.height,
+            settings.tile_size,
+            settings.tile_order,
+        )
+    }
+
+    /// Take a range of samples in every pixel of some tiles and add them to a film.
+    ///
+    /// Tiles are handed out to the workers, which collect their samples in film tiles.
+    /// Floating-point sums depend on the order of addition and tiles overlap where the
+    /// filter extends past their edges, so the film tiles are added to the film in the
+    /// order of `tiles` to keep images independent of the number of workers.
+    /// Tiles that are not completed before `cancel` is cancelled are discarded.
+    ///
+    /// # Arguments
+    /// * `film` - The film receiving the samples.
+    /// * `stats` - Statistics of the samples taken so far in each pixel; updated.
+    /// * `tiles` - Each tile to render and the indices of the samples to take in its pixels.
+    /// * `progress` - Called with the index in `tiles` of each tile once it has been added
+    ///   to the film and the number of pixels completed so far.
+    /// * `cancel` - Checked by the workers between pixels.
+    ///
+    /// # Returns
+    /// The number of samples taken or an error if a worker panicked.
+    fn render_tiles(
+        &self,
+        film: &Film,
+        stats: &mut [PixelStats],
+        tiles: Vec<(Tile, Range<u32>)>,
+        progress: &mut dyn FnMut(usize, u64),
+        cancel: &CancelToken,
+    ) -> Result<u64, WorkerPanic> {
+        let settings = &self.state.settings;
+        let width = settings.width as usize;
+        let mut completed = 0;
+        let mut num_samples = 0;
+
+        let items: Vec<_> = tiles
+            .into_iter()
+            .enumerate()
+            .map(|(i, (tile, samples))| {
+                let tile_stats = tile
+                    .ys
+                    .clone()
+                    .flat_map(|y| {
+                        let start = y as usize * width;
+                        stats[start + tile.xs.start as usize..start + tile.xs.end as usize]
+                            .iter()
+                            .copied()
+                    })
+                    .collect::<Vec<_>>();
+                (i, tile, samples, tile_stats)
+            })
+            .collect();
+        ThreadPool::new(settings.num_workers).map_ordered(
+            items,
+            |(i, tile, samples, mut tile_stats)| {
+                let film_tile = film.tile(tile.xs.clone(), tile.ys.clone());
+                let result = render_tile(
+                    &self.state,
+                    &tile,
+                    samples,
+                    &mut tile_stats,
+                    film_tile,
+                    cancel,
+                );
+                (i, tile, tile_stats, result)
+            },
+            |(i, tile, tile_stats, result)| {
+                let (film_tile, count) = match result {
+                    Some(result) => result,
+                    None => return,
+                };
+                film.merge_tile(&film_tile);
+                let tile_width = tile.xs.len();
+                for (y, row) in tile.ys.clone().zip(tile_stats.chunks(tile_width)) {
+                    let start = y as usize * width + tile.xs.start as usize;
+                    stats[start..start + tile_width].copy_from_slice(row);
+                }
+                num_samples += count;
+                completed += tile.area();
+                progress(i, completed);
+            },
+        )?;
+        Ok(num_samples)
+    }
+}
+
+/// A render in progress that takes one sample per pixel in each pass
+///
+/// Created by `Renderer::start_progressive` or `Renderer::resume_progressive`.
+pub struct ProgressiveRender<'a, S> {
+    renderer: &'a Renderer<S>,
+    film: Film,
+    /// Statistics of the samples taken in each pixel
+    stats: Vec<PixelStats>,
+    tiles: Vec<Tile>,
+    /// Number of passes completed by each tile
+    tile_passes: Vec<u32>,
+    /// Whether adaptive sampling found that every pixel had converged
+    converged: bool,
+}
+
+impl<'a, S: Surface> ProgressiveRender<'a, S> {
+    /// The film holding every sample taken so far
+    pub fn film(&self) -> &Film {
+        &self.film
+    }
+
+    /// Number of passes completed by every tile of the image
+    pub fn passes(&self) -> u32 {
+        self.tile_passes.iter().copied().min().unwrap_or(0)
+    }
+
+    /// Whether all passes have been rendered
+    pub fn is_finished(&self) -> bool {
+        self.converged || self.passes() >= self.renderer.state.settings.samples_per_pixel
+    }
+
+    /// Render the next pass, or the rest of it if the previous attempt was cancelled.
+    ///
+    /// # Arguments
+    /// * `progress` - Called from the calling thread each time a tile is completed,
+    ///   along with the film holding every sample taken so far.
+    /// * `cancel` - Checked by the workers between pixels.
+    ///
+    /// # Returns
+    /// An error if cancelled before the pass was completed or if a worker panicked.
+    /// The tiles completed before then are kept.
+    pub fn render_pass<F>(
+        &mut self,
+        mut progress: F,
+        cancel: &CancelToken,
+    ) -> Result<(), RenderError>
+    where
+        F: FnMut(Progress, &Film),
+    {
+        let settings = &self.renderer.state.settings;
+        let pass = self.passes();
+        let pixels = settings.width as u64 * settings.height as u64;
+        let total = pixels * settings.samples_per_pixel as u64;
+        let (remaining, indices): (Vec<_>, Vec<_>) = self
+            .tiles
+            .iter()
+            .zip(&self.tile_passes)
+            .enumerate()
+            .filter(|(_, (_, &passes))| passes == pass)
+            .map(|(i, (tile, _))| ((tile.clone(), pass..pass + 1), i))
+            .unzip();
+        let previously_completed =
+            pixels - remaining.iter().map(|(tile, _)| tile.area()).sum::<u64>();
+        let film = &self.film;
+        let tile_passes = &mut self.tile_passes;
+        // Completed tiles are recorded as they are added to the film, even if a worker panics
+        let num_samples = self.renderer.render_tiles(
+            film,
+            &mut self.stats,
+            remaining,
+            &mut |i, completed| {
+                tile_passes[indices[i]] += 1;
+                let progress_ = Progress {
+                    completed: pass as u64 * pixels + previously_completed + completed,
+                    total,
+                    pass,
+                };
+                progress(progress_, film)
+            },
+            cancel,
+        )?;
+        if cancel.is_cancelled() {
+            return Err(RenderError::Cancelled);
+        }
+        // A whole pass without samples means that adaptive sampling has finished every pixel
+        if previously_completed == 0 && num_samples == 0 {
+            self.converged = true;
+        }
+        Ok(())
+    }
+
+    /// The image formed by all samples taken so far
+    pub fn to_framebuffer(&self) -> Framebuffer {
+        self.film.to_framebuffer()
+    }
+
+    /// Record the state of the render so that it can be resumed later.
+    ///
+    /// # Arguments
+    /// * `scene_hash` - Identifies the scene; see `Renderer::resume_progressive`.
+    pub fn checkpoint(&self, scene_hash: u64) -> Checkpoint {
+        let settings = &self.renderer.state.settings;
+        Checkpoint {
+            width: settings.width,
+            height: settings.height,
+            seed: settings.seed,
+            settings_hash: settings.fingerprint(),
+            scene_hash,
+            tile_passes: self.tile_passes.clone(),
+            film: self.film.pixels(),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+/// Take a range of samples in each pixel of a tile, adding them to a film tile.
+///
+/// # Arguments
+/// * `stats` - Sample statistics of the pixels of the tile in row-major order; updated.
+///
+/// # Returns
+/// The film tile and the number of samples taken, or None if cancelled.
+fn render_tile<S: Surface>(
+    state: &RenderState<S>,
+    tile: &Tile,
+    samples: Range<u32>,
+    stats: &mut [PixelStats],
+    mut film_tile: FilmTile,
+    cancel: &CancelToken,
+) -> Option<(FilmTile, u64)> {
+    let settings = &state.settings;
+    let mut sampler = settings
+        .sampler
+        .build(settings.samples_per_pixel, settings.seed);
+    let mut count = 0;
+    let mut pixel_stats = stats.iter_mut();
+    for y in tile.ys.clone() {
+        for x in tile.xs.clone() {
+            if cancel.is_cancelled() {
+                return None;
+            }
+            count += render_pixel(
+                state,
+                x,
+                y,
+                samples.clone(),
+                pixel_stats.next().unwrap(),
+                &mut *sampler,
+                &mut film_tile,
+            ) as u64;
+        }
+    }
+    Some((film_tile, count))
+}
+
+/// Take a range of samples of a pixel, adding them to a film tile.
+///
+/// Stops early if adaptive sampling finds that the pixel has converged.
+///
+/// # Returns
+/// The number of samples taken.
+fn render_pixel<S: Surface>(
+    state: &RenderState<S>,
+    x: u32,
+    y: u32,
+    samples: Range<u32>,
+    stats: &mut PixelStats,
+    sampler: &mut dyn Sampler,
+    tile: &mut FilmTile,
+) -> u32 {
+    let settings = &state.settings;
+    let mut count = 0;
+    for sample in samples {
+        if let Some(adaptive) = &settings.adaptive {
+            if adaptive.is_converged(stats) {
+                break;
+            }
+        }
+        count += 1;
+        sampler.start_pixel_sample((x, y), sample);
+        let (dx, dy) = sampler.get_2d();
+        // Raster position measured from the top left corner
+        let (px, py) = (x as f64 + dx, y as f64 + dy);
+        // (u, v) measure from bottom left corner
+        let u = px / ((settings.width - 1) as f64);
+        let v = (settings.height as f64 - py) / ((settings.height - 1) as f64);
+        let ray = state.camera.get_ray(u, v, sampler);
+        let colour = ray_colour(&ray, state, settings.max_depth, None, sampler);
+        stats.add(luminance(colour));
+        tile.add_sample((px, py), colour);
+    }
+    count
+}
+
+/// Radiance arriving along a ray.
+///
+/// # Arguments
+/// * `scatter_pdf` - Density with which the ray direction was sampled by the material it
+///   scattered from. None for camera rays and rays scattered by delta materials.
+fn ray_colour<S: Surface>(
+    ray: &RayR3,
+    state: &RenderState<S>,
+    depth: u32,
+    scatter_pdf: Option<f64>,
+    sampler: &mut dyn Sampler,
+) -> Colour {
+    // Exceeded ray bounce limit; no more light is gathered
+    if depth == 0 {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+
+    // 0.001 is to prevent collisions with the object the ray is leaving; the "acne" problem.
+    if let Some(intersection) = state.surface.intersect(ray, 0.001, f64::INFINITY) {
+        let mut colour = intersection.emitted();
+        if let (Some(light), Some(scatter_pdf)) = (intersection.light, scatter_pdf) {
+            let light_pdf = state.lights.pdf_surface(light, &ray.origin, &ray.direction);
+            colour *= state.emission_weight(scatter_pdf, light_pdf);
+        }
+
+        let material = intersection.material;
+        let sample_lights = state.settings.integrator != Integrator::Path && !material.is_delta();
+        if sample_lights {
+            colour += sample_direct(&intersection, ray, state, sampler);
+        }
+
+        if let Some((attenuation, scatter)) = intersection.scatter(ray, sampler) {
+            let pdf = if material.is_delta() {
+                None
+            } else {
+                Some(material.pdf(
+                    &scatter.direction.as_unit(),
+                    &-ray.direction.as_unit(),
+                    &intersection.shading_normal,
+                    intersection.front_face,
+                ))
+            };
+            colour +=
+                attenuation.elementwise_mul(ray_colour(&scatter, state, depth - 1, pdf, sampler));
+        }
+        return colour;
+    }
+
+    let mut colour = state.background.radiance(&ray.direction);
+    if let Some(scatter_pdf) = scatter_pdf {
+        let light_pdf = state
+            .lights
+            .pdf_environment(&*state.background, &ray.direction);
+        colour *= state.emission_weight(scatter_pdf, light_pdf);
+    }
+    colour
+}
+
+impl<S> RenderState<S> {
+    /// Weight of light found by a ray scattered from a non-delta material.
+    ///
+    /// # Arguments
+    /// * `scatter_pdf` - Density with which the material sampled the ray direction.
+    /// * `light_pdf` - Density with which light sampling would have produced the direction.
+    fn emission_weight(&self, scatter_pdf: f64, light_pdf: f64) -> f64 {
+        if light_pdf == 0.0 {
+            // The light cannot be sampled explicitly
+            return 1.0;
+        }
+        match self.settings.integrator {
+            Integrator::Path => 1.0,
+            Integrator::Nee => 0.0,
+            Integrator::Mis => self.settings.mis_heuristic.weight(scatter_pdf, light_pdf),
+        }
+    }
+}
+
+/// Light arriving at an intersection directly from a randomly sampled light source
+/// and scattered back along the incident ray.
+fn sample_direct<S: Surface>(
+    intersection: &Intersection<'_>,
+    ray: &RayR3,
+    state: &RenderState<S>,
+    sampler: &mut dyn Sampler,
+) -> Colour {
+    let black = Colour::new(0.0, 0.0, 0.0);
+    let point = intersection.point;
+    let sample = match state.lights.sample(
+        &point,
+        &*state.background,
+        sampler.get_1d(),
+        sampler.get_2d(),
+    ) {
+        Some(sample) => sample,
+        None => return black,
+    };
+    // Light from behind the geometric surface would leak through it
+    if !intersection.same_side(&sample.direction) {
+        return black;
+    }
+    let material = intersection.material;
+    let wo = -ray.direction.as_unit();
+    let f = material.eval(
+        &sample.direction,
+        &wo,
+        &intersection.shading_normal,
+        intersection.front_face,
+    );
+    if f == black || sample.radiance == black {
+        return black;
+    }
+
+    // Shadow ray; stop just short of the light so that it does not occlude itself.
+    let shadow = RayR3::new(point, sample.direction);
+    let t_max = sample.distance * (1.0 - 1e-4);
+    if state.surface.intersect(&shadow, 0.001, t_max).is_some() {
+        return black;
+    }
+
+    let weight = match state.settings.integrator {
+        Integrator::Mis => {
+            let scatter_pdf = material.pdf(
+                &sample.direction,
+                &wo,
+                &intersection.shading_normal,
+                intersection.front_face,
+            );
+            state.settings.mis_heuristic.weight(sample.pdf, scatter_pdf)
+        }
+        _ => 1.0,
+    };
+    f.elementwise_mul(sample.radiance) * (weight / sample.pdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_counts_are_saved_linearly() {
+        let mut framebuffer = Framebuffer::new(4, 1);
+        framebuffer.samples = vec![0, 1, 2, 4];
+        let path = std::env::temp_dir().join(format!("raytracer-spp-{}.png", std::process::id()));
+        framebuffer.sample_count_image().save_linear(&path).unwrap();
+        let image = image::open(&path).unwrap().to_rgb8();
+        let values: Vec<u8> = image.pixels().map(|p| p[0]).collect();
+        assert_eq!(values, [0, 64, 128, 255]);
+    }
+}
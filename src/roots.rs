@@ -0,0 +1,194 @@
+//! Real roots of low degree polynomials
+
+/// Real roots of a x^2 + b x + c in ascending order.
+///
+/// Falls back to the linear equation if `a` is zero, returning its root twice.
+/// Returns None if there are no real roots.
+pub fn quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        return Some((-c / b, -c / b));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    // Avoids cancellation between -b and the square root
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    if q == 0.0 {
+        // b and c are both zero
+        return Some((0.0, 0.0));
+    }
+    let (x0, x1) = (q / a, c / q);
+    Some((x0.min(x1), x0.max(x1)))
+}
+
+/// The largest real root of x^3 + a x^2 + b x + c.
+pub fn cubic_largest(a: f64, b: f64, c: f64) -> f64 {
+    // Substitute x = y - a / 3 to get y^3 + p y + q
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+    let y = if discriminant > 0.0 {
+        // One real root
+        let s = discriminant.sqrt();
+        (-q / 2.0 + s).cbrt() + (-q / 2.0 - s).cbrt()
+    } else if p == 0.0 {
+        0.0
+    } else {
+        // Three real roots; the largest has angle 0
+        let r = (-p / 3.0).sqrt();
+        let cos = (-q / (2.0 * r * r * r)).clamp(-1.0, 1.0);
+        2.0 * r * (cos.acos() / 3.0).cos()
+    };
+    let x = y - a / 3.0;
+    polish(
+        x,
+        |x| ((x + a) * x + b) * x + c,
+        |x| (3.0 * x + 2.0 * a) * x + b,
+    )
+}
+
+/// Real roots of x^4 + a x^3 + b x^2 + c x + d in ascending order.
+///
+/// Uses Ferrari's method followed by Newton iterations on the original polynomial
+/// to recover the precision lost in the intermediate steps.
+pub fn quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // Substitute x = y - a / 4 to get y^4 + p y^2 + q y + r
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut ys = Vec::with_capacity(4);
+    if q.abs() <= 1e-12 * (1.0 + p.abs() + r.abs()) {
+        // Biquadratic: a quadratic in y^2
+        if let Some((z0, z1)) = quadratic(1.0, p, r) {
+            for &z in [z0, z1].iter().filter(|&&z| z >= 0.0) {
+                let y = z.sqrt();
+                ys.push(-y);
+                ys.push(y);
+            }
+        }
+    } else {
+        // Write the quartic as a difference of squares using a root m > 0 of the
+        // resolvent cubic, which exists because the cubic is negative at 0.
+        let m = cubic_largest(p, p * p / 4.0 - r, -q * q / 8.0).max(0.0);
+        let s = (2.0 * m).sqrt();
+        if s > 0.0 {
+            let t = q / (2.0 * s);
+            for &(linear, constant) in [(-s, p / 2.0 + m + t), (s, p / 2.0 + m - t)].iter() {
+                if let Some((y0, y1)) = quadratic(1.0, linear, constant) {
+                    ys.push(y0);
+                    ys.push(y1);
+                }
+            }
+        }
+    }
+
+    let f = |x: f64| (((x + a) * x + b) * x + c) * x + d;
+    let df = |x: f64| ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+    let mut xs: Vec<f64> = ys.into_iter().map(|y| polish(y - a / 4.0, f, df)).collect();
+    xs.sort_by(f64::total_cmp);
+    xs
+}
+
+/// Refine a root of `f` with a few Newton iterations, keeping the best estimate.
+fn polish<F, D>(x: f64, f: F, df: D) -> f64
+where
+    F: Fn(f64) -> f64,
+    D: Fn(f64) -> f64,
+{
+    let mut best = x;
+    let mut best_error = f(x).abs();
+    let mut x = x;
+    for _ in 0..4 {
+        let slope = df(x);
+        if slope == 0.0 || best_error == 0.0 {
+            break;
+        }
+        x -= f(x) / slope;
+        let error = f(x).abs();
+        if error.is_nan() || error >= best_error {
+            break;
+        }
+        best = x;
+        best_error = error;
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(actual: &[f64], expected: &[f64]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_eq!(quadratic(1.0, -3.0, 2.0), Some((1.0, 2.0)));
+        assert_eq!(quadratic(-1.0, 3.0, -2.0), Some((1.0, 2.0)));
+        assert_eq!(quadratic(1.0, 0.0, 1.0), None);
+        // Linear
+        assert_eq!(quadratic(0.0, 2.0, -1.0), Some((0.5, 0.5)));
+        assert_eq!(quadratic(0.0, 0.0, 1.0), None);
+    }
+
+    #[test]
+    fn quadratic_avoids_cancellation() {
+        // Roots 1e-8 and 1e8
+        let (x0, x1) = quadratic(1.0, -(1e8 + 1e-8), 1.0).unwrap();
+        assert!((x0 - 1e-8).abs() < 1e-20);
+        assert!((x1 - 1e8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cubic_largest_root() {
+        // (x - 1)(x - 2)(x - 3)
+        assert!((cubic_largest(-6.0, 11.0, -6.0) - 3.0).abs() < 1e-12);
+        // (x - 2)(x^2 + 1)
+        assert!((cubic_largest(-2.0, 1.0, -2.0) - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn quartic_four_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(&quartic(-10.0, 35.0, -50.0, 24.0), &[1.0, 2.0, 3.0, 4.0]);
+        // (x + 5)(x + 0.5)(x - 0.25)(x - 7)
+        let (a, b, c, d) = (-1.75, -35.625, -8.5, 4.375);
+        assert_roots(&quartic(a, b, c, d), &[-5.0, -0.5, 0.25, 7.0]);
+    }
+
+    #[test]
+    fn quartic_two_roots() {
+        // (x^2 - 4)(x^2 + 4)
+        assert_roots(&quartic(0.0, 0.0, 0.0, -16.0), &[-2.0, 2.0]);
+        // (x - 1)(x - 3)(x^2 + 2x + 5)
+        assert_roots(&quartic(-2.0, 0.0, -14.0, 15.0), &[1.0, 3.0]);
+    }
+
+    #[test]
+    fn quartic_biquadratic() {
+        // (x^2 - 1)(x^2 - 4)
+        assert_roots(&quartic(0.0, -5.0, 0.0, 4.0), &[-2.0, -1.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn quartic_no_roots() {
+        assert!(quartic(0.0, 1.0, 0.0, 1.0).is_empty());
+        assert!(quartic(0.0, 0.0, 0.0, 1.0).is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+use crate::samplers::{hash, mix_bits, pixel_id, to_unit_float, Sampler};
+
+/// Bases of the Halton sequence for the first dimensions
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Samples from the Halton sequence
+///
+/// Dimension `i` is the radical inverse of the sample index in base `PRIMES[i]`.
+/// Each pixel uses a differently scrambled sequence: every digit is shifted by a random
+/// amount that depends on the pixel, the dimension and the digit position.
+/// Dimensions beyond the number of bases are sampled independently at random.
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: u64,
+    index: u64,
+    dimension: usize,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next_dimension(&mut self) -> f64 {
+        let scramble = hash(&[self.seed, self.pixel, self.dimension as u64]);
+        let value = match PRIMES.get(self.dimension) {
+            Some(&base) => scrambled_radical_inverse(base, self.index, scramble),
+            None => to_unit_float(hash(&[scramble, self.index])),
+        };
+        self.dimension += 1;
+        value
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel_id(pixel);
+        self.index = index as u64;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.next_dimension()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let x = self.next_dimension();
+        (x, self.next_dimension())
+    }
+}
+
+/// Radical inverse of `index` in `base` with each digit shifted by a pseudo-random amount.
+///
+/// Shifting every digit position by the same amount for all indices preserves the
+/// stratification of the sequence.
+fn scrambled_radical_inverse(base: u64, mut index: u64, scramble: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut result = 0.0;
+    let mut position = 0;
+    // Continue past the last non-zero digit of the index since zeros are shifted too
+    while inv_base_m > f64::EPSILON {
+        let digit = index % base;
+        index /= base;
+        let shift = mix_bits(scramble.wrapping_add(position)) % base;
+        inv_base_m *= inv_base;
+        result += ((digit + shift) % base) as f64 * inv_base_m;
+        position += 1;
+    }
+    result.min(1.0 - f64::EPSILON)
+}
@@ -0,0 +1,33 @@
+use crate::samplers::{pixel_id, Sampler};
+use crate::sampling::{sample_rng, SampleRng};
+use rand::Rng;
+
+/// Samples every dimension independently and uniformly at random
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    seed: u64,
+    rng: SampleRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: sample_rng(seed, 0, 0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.rng = sample_rng(self.seed, pixel_id(pixel), index as u64);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        self.rng.gen()
+    }
+}
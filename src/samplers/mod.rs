@@ -0,0 +1,221 @@
+pub mod halton;
+pub mod independent;
+pub mod sobol;
+pub mod stratified;
+
+pub use halton::HaltonSampler;
+pub use independent::IndependentSampler;
+pub use sobol::SobolSampler;
+pub use stratified::StratifiedSampler;
+
+use anyhow::anyhow;
+use std::str::FromStr;
+
+/// A source of sample values in [0, 1) for rendering
+///
+/// Each pixel sample is a point in a high-dimensional unit hypercube.
+/// Its dimensions are requested one or two at a time in a consistent order
+/// (pixel position, then lens position, then light and scattering samples at each bounce)
+/// so that samplers can distribute the early, most important dimensions well.
+///
+/// The values depend only on the pixel, the sample index, the sampler settings and the seed
+/// so that renders are reproducible regardless of the order in which samples are taken.
+pub trait Sampler: Send {
+    /// Begin generating sample `index` of a pixel.
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32);
+
+    /// The next dimension of the current sample
+    fn get_1d(&mut self) -> f64;
+
+    /// The next two dimensions of the current sample
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+/// A choice of sampler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    /// Create a sampler.
+    ///
+    /// # Arguments
+    /// * `samples_per_pixel` - The number of samples that will be taken in each pixel.
+    /// * `seed` - Random seed.
+    pub fn build(self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(samples_per_pixel, seed)),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "independent" => Ok(SamplerKind::Independent),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(anyhow!(
+                "unknown sampler '{}'; expected independent, stratified, halton or sobol",
+                s
+            )),
+        }
+    }
+}
+
+/// A single 64-bit identifier of a pixel
+fn pixel_id((x, y): (u32, u32)) -> u64 {
+    (y as u64) << 32 | x as u64
+}
+
+/// Hash a sequence of integers.
+fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x51_7cc1_b727_220a, |h, &v| mix_bits(h ^ mix_bits(v)))
+}
+
+/// Scramble the bits of an integer (the splitmix64 finaliser).
+fn mix_bits(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A float in [0, 1) from the high bits of a random integer
+fn to_unit_float(bits: u64) -> f64 {
+    (bits >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+/// Element `i` of a pseudo-random permutation of 0..n selected by `seed`.
+///
+/// Uses Andrew Kensler's hash-based permutation, which needs no storage.
+fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    let p = seed;
+    // Cycle-walk until the permuted value falls in range
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    ((i as u64 + p as u64) % n as u64) as u32
+}
+
+/// Shuffle sample indices in groups of `n`.
+///
+/// Indices `0..n` are permuted amongst themselves, as are `n..2n` and so on,
+/// with a different permutation for each group.
+fn shuffle_index(index: u32, n: u32, seed: u64) -> u32 {
+    let round = index / n;
+    let permutation_seed = hash(&[seed, round as u64]) as u32;
+    round * n + permutation_element(index % n, n, permutation_seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 4] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    /// The first dimensions of a pixel sample, requested as alternating 1D and 2D samples
+    fn sample_values(sampler: &mut dyn Sampler, pixel: (u32, u32), index: u32) -> Vec<f64> {
+        sampler.start_pixel_sample(pixel, index);
+        let mut values = Vec::new();
+        for _ in 0..12 {
+            values.push(sampler.get_1d());
+            let (x, y) = sampler.get_2d();
+            values.extend([x, y]);
+        }
+        values
+    }
+
+    #[test]
+    fn values_are_in_unit_interval() {
+        for &kind in &KINDS {
+            for &samples_per_pixel in &[1, 7, 16] {
+                let mut sampler = kind.build(samples_per_pixel, 3);
+                for &pixel in &[(0, 0), (5, 9), (u32::MAX, u32::MAX)] {
+                    // Progressive renders take samples beyond the planned count
+                    for index in 0..3 * samples_per_pixel {
+                        for value in sample_values(&mut *sampler, pixel, index) {
+                            assert!((0.0..1.0).contains(&value), "{:?} gave {}", kind, value);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn low_discrepancy_samplers_are_deterministic() {
+        for &kind in &[SamplerKind::Halton, SamplerKind::Sobol] {
+            let values = |seed: u64, pixel: (u32, u32)| {
+                let mut sampler = kind.build(16, seed);
+                (0..16)
+                    .map(|index| sample_values(&mut *sampler, pixel, index))
+                    .collect::<Vec<_>>()
+            };
+            let samples = values(1, (2, 3));
+            assert_eq!(samples, values(1, (2, 3)), "{:?}", kind);
+            assert_ne!(samples, values(1, (3, 2)), "{:?}", kind);
+            assert_ne!(samples, values(2, (2, 3)), "{:?}", kind);
+
+            // Each dimension takes a different sequence of values over the samples
+            let dimension = |d: usize| samples.iter().map(|s| s[d]).collect::<Vec<_>>();
+            for d in 0..samples[0].len() {
+                for e in 0..d {
+                    assert_ne!(dimension(d), dimension(e), "{:?}", kind);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse() {
+        for &kind in &KINDS {
+            let name = format!("{:?}", kind).to_lowercase();
+            assert_eq!(name.parse::<SamplerKind>().unwrap(), kind);
+        }
+        assert!("sobol2".parse::<SamplerKind>().is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::samplers::{hash, pixel_id, shuffle_index, Sampler};
+
+/// Samples from the first two dimensions of the Sobol sequence with Owen scrambling
+///
+/// Every one or two requested dimensions use their own randomly scrambled copy of the
+/// two-dimensional Sobol sequence with the sample indices randomly shuffled
+/// ("padding"), which keeps the excellent 2D stratification of Sobol points without
+/// the correlation artefacts of high-dimensional Sobol sequences.
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    samples_per_pixel: u32,
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Shuffled sample index and scrambling seed of the next dimension
+    fn next_dimension(&mut self) -> (u32, u64) {
+        let h = hash(&[self.seed, self.pixel, self.dimension]);
+        self.dimension += 1;
+        (shuffle_index(self.index, self.samples_per_pixel, h), h)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel_id(pixel);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (index, h) = self.next_dimension();
+        to_float(owen_scramble(index.reverse_bits(), h as u32))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, h) = self.next_dimension();
+        (
+            to_float(owen_scramble(index.reverse_bits(), h as u32)),
+            to_float(owen_scramble(sobol_dimension_1(index), (h >> 32) as u32)),
+        )
+    }
+}
+
+/// The second dimension of the Sobol sequence as a 32-bit fixed-point fraction.
+///
+/// The first dimension is the bit-reversed index.
+fn sobol_dimension_1(mut index: u32) -> u32 {
+    // Direction numbers of the second dimension form the Pascal matrix mod 2
+    let mut v = 1u32 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+/// Owen-scramble a 32-bit fixed-point fraction using the Laine-Karras hash.
+///
+/// Randomly permutes the fraction such that stratification by powers of two is preserved.
+fn owen_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20_adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x0552_6c56);
+    v ^= v.wrapping_mul(0x53a2_2864);
+    v.reverse_bits()
+}
+
+/// A float in [0, 1) from a 32-bit fixed-point fraction
+fn to_float(v: u32) -> f64 {
+    v as f64 * (1.0 / (1u64 << 32) as f64)
+}
@@ -0,0 +1,104 @@
+use crate::samplers::{hash, pixel_id, shuffle_index, to_unit_float, Sampler};
+
+/// Jittered stratified sampling
+///
+/// Each dimension is divided into one stratum per sample (a grid of strata for pairs of
+/// dimensions) and every sample of a pixel falls in a different, randomly chosen stratum.
+/// Dimensions are shuffled independently of each other.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    /// Grid of strata used for 2D samples; `x_strata * y_strata >= samples_per_pixel`.
+    x_strata: u32,
+    y_strata: u32,
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let x_strata = ((samples_per_pixel as f64).sqrt() as u32).max(1);
+        let y_strata = samples_per_pixel.div_ceil(x_strata);
+        Self {
+            samples_per_pixel,
+            x_strata,
+            y_strata,
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Hash identifying the current dimension of the current pixel
+    fn dimension_hash(&mut self) -> u64 {
+        let h = hash(&[self.seed, self.pixel, self.dimension]);
+        self.dimension += 1;
+        h
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel_id(pixel);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let h = self.dimension_hash();
+        let n = self.samples_per_pixel;
+        let stratum = shuffle_index(self.index, n, h) % n;
+        let jitter = to_unit_float(hash(&[h, self.index as u64]));
+        (stratum as f64 + jitter) / n as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let h = self.dimension_hash();
+        let n = self.x_strata * self.y_strata;
+        let stratum = shuffle_index(self.index, n, h) % n;
+        let (x, y) = (stratum % self.x_strata, stratum / self.x_strata);
+        let jitter_x = to_unit_float(hash(&[h, self.index as u64, 0]));
+        let jitter_y = to_unit_float(hash(&[h, self.index as u64, 1]));
+        (
+            (x as f64 + jitter_x) / self.x_strata as f64,
+            (y as f64 + jitter_y) / self.y_strata as f64,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stratified_puts_one_sample_in_each_stratum() {
+        for &samples_per_pixel in &[16, 7, 10] {
+            let mut sampler = StratifiedSampler::new(samples_per_pixel, 5);
+            let (x_strata, y_strata) = (sampler.x_strata, sampler.y_strata);
+            for &pixel in &[(0, 0), (3, 1)] {
+                let mut strata_1d = vec![vec![false; samples_per_pixel as usize]; 4];
+                let mut strata_2d = vec![vec![false; (x_strata * y_strata) as usize]; 4];
+                for index in 0..samples_per_pixel {
+                    sampler.start_pixel_sample(pixel, index);
+                    for dimension in 0..4 {
+                        let value = sampler.get_1d();
+                        let stratum = (value * samples_per_pixel as f64) as usize;
+                        assert!(!strata_1d[dimension][stratum]);
+                        strata_1d[dimension][stratum] = true;
+
+                        let (x, y) = sampler.get_2d();
+                        let stratum =
+                            (y * y_strata as f64) as u32 * x_strata + (x * x_strata as f64) as u32;
+                        assert!(!strata_2d[dimension][stratum as usize]);
+                        strata_2d[dimension][stratum as usize] = true;
+                    }
+                }
+                assert!(strata_1d.iter().flatten().all(|&s| s));
+            }
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use crate::vec3::{Vec3, VecR3};
+use rand_pcg::Pcg32;
+use std::f64::consts::PI;
+
+/// The random number generator used while rendering
+pub type SampleRng = Pcg32;
+
+/// Create the random number generator for one sample of one pixel.
+///
+/// Each sample gets an independent generator so that the result of a render
+/// does not depend on the order in which samples are taken.
+///
+/// # Arguments
+/// * `seed` - The seed of the render.
+/// * `pixel` - Index of the pixel in the image.
+/// * `sample` - Index of the sample within the pixel.
+pub fn sample_rng(seed: u64, pixel: u64, sample: u64) -> SampleRng {
+    Pcg32::new(splitmix64(seed ^ splitmix64(sample)), pixel)
+}
+
+/// Scramble the bits of an integer; used to derive generator states from seeds.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A piecewise-constant probability distribution on [0, 1)
+///
+/// The interval is divided into equal-width pieces with density proportional to
+/// the given non-negative function values.
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    function: Vec<f64>,
+    /// Cumulative distribution function at the piece boundaries; `cdf.len() == n + 1`.
+    cdf: Vec<f64>,
+    /// Integral of the function over [0, 1)
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// Create a distribution from function values of equal-width pieces.
+    ///
+    /// If all values are zero then the distribution is uniform.
+    pub fn new(function: Vec<f64>) -> Self {
+        let n = function.len();
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for (i, f) in function.iter().enumerate() {
+            cdf.push(cdf[i] + f / n as f64);
+        }
+        let integral = cdf[n];
+        if integral > 0.0 {
+            for c in cdf.iter_mut() {
+                *c /= integral;
+            }
+        } else {
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n as f64;
+            }
+        }
+        Self {
+            function,
+            cdf,
+            integral,
+        }
+    }
+
+    /// Number of pieces
+    pub fn len(&self) -> usize {
+        self.function.len()
+    }
+
+    /// Whether the distribution has no pieces
+    pub fn is_empty(&self) -> bool {
+        self.function.is_empty()
+    }
+
+    /// Integral of the function over [0, 1)
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Sample a point in [0, 1)
+    ///
+    /// # Arguments
+    /// * `u` - A uniform random number in [0, 1).
+    ///
+    /// # Returns
+    /// The sampled point, its probability density and the index of the piece containing it.
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        let index = self.find_piece(u);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (u - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+        let x = ((index as f64 + offset) / self.len() as f64).min(1.0 - f64::EPSILON);
+        (x, self.pdf_piece(index), index)
+    }
+
+    /// Sample a piece index with probability proportional to its function value.
+    ///
+    /// # Returns
+    /// The index and its probability.
+    pub fn sample_discrete(&self, u: f64) -> (usize, f64) {
+        let index = self.find_piece(u);
+        (index, self.cdf[index + 1] - self.cdf[index])
+    }
+
+    /// Probability of sampling a piece index with `sample_discrete`.
+    pub fn discrete_pdf(&self, index: usize) -> f64 {
+        self.cdf[index + 1] - self.cdf[index]
+    }
+
+    /// Probability density of `sample_continuous` at a point in [0, 1).
+    pub fn pdf(&self, x: f64) -> f64 {
+        let index = ((x * self.len() as f64) as usize).min(self.len() - 1);
+        self.pdf_piece(index)
+    }
+
+    fn pdf_piece(&self, index: usize) -> f64 {
+        (self.cdf[index + 1] - self.cdf[index]) * self.len() as f64
+    }
+
+    /// Index of the piece whose cdf range contains u, skipping zero-probability pieces.
+    fn find_piece(&self, u: f64) -> usize {
+        let i = self.cdf[1..].partition_point(|&c| c <= u);
+        i.min(self.len() - 1)
+    }
+}
+
+/// A piecewise-constant probability distribution on [0, 1)^2
+///
+/// Sampled by first choosing a row from the marginal distribution
+/// then a column from that row's conditional distribution.
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Create a distribution from a row-major grid of function values.
+    pub fn new(function: &[f64], width: usize, height: usize) -> Self {
+        let conditional: Vec<Distribution1D> = function
+            .chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Sample a point (x, y) in [0, 1)^2 with y indexing rows.
+    ///
+    /// # Returns
+    /// The point and its probability density.
+    pub fn sample_continuous(&self, u: (f64, f64)) -> ((f64, f64), f64) {
+        let (y, pdf_y, row) = self.marginal.sample_continuous(u.1);
+        let (x, pdf_x, _) = self.conditional[row].sample_continuous(u.0);
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    /// Probability density of `sample_continuous` at a point.
+    pub fn pdf(&self, (x, y): (f64, f64)) -> f64 {
+        let height = self.conditional.len();
+        let row = ((y * height as f64) as usize).min(height - 1);
+        self.marginal.pdf(y) * self.conditional[row].pdf(x)
+    }
+}
+
+/// Sample a direction uniformly from the unit sphere.
+///
+/// # Arguments
+/// * `u` - Uniform random numbers in [0, 1).
+pub fn uniform_sphere(u: (f64, f64)) -> VecR3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Probability density of `uniform_sphere` with respect to solid angle.
+pub fn uniform_sphere_pdf() -> f64 {
+    1.0 / (4.0 * PI)
+}
+
+/// Sample a point uniformly from the unit disk.
+///
+/// Uses Shirley's concentric mapping, which preserves the stratification of `u`.
+///
+/// # Arguments
+/// * `u` - Uniform random numbers in [0, 1).
+pub fn uniform_disk(u: (f64, f64)) -> (f64, f64) {
+    let (a, b) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+/// Two unit vectors that form an orthonormal basis together with a unit vector `w`.
+pub fn orthonormal_basis(w: &VecR3) -> (VecR3, VecR3) {
+    // Building the basis from whichever axis is least aligned with w avoids degeneracy
+    let a = if w.x.abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let v = w.cross(a).as_unit();
+    let u = w.cross(v);
+    (u, v)
+}
+
+/// Multiple importance sampling weight of a sample using the balance heuristic.
+///
+/// # Arguments
+/// * `pdf` - Density of the strategy that generated the sample.
+/// * `other_pdf` - Density with which the other strategy would have generated the sample.
+pub fn balance_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    pdf / (pdf + other_pdf)
+}
+
+/// Multiple importance sampling weight of a sample using the power heuristic with exponent 2.
+///
+/// # Arguments
+/// * `pdf` - Density of the strategy that generated the sample.
+/// * `other_pdf` - Density with which the other strategy would have generated the sample.
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a.is_infinite() {
+        return 1.0;
+    }
+    a / (a + b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distribution_1d_follows_weights() {
+        let function = vec![1.0, 0.0, 3.0, 2.0, 0.0, 4.0];
+        let distribution = Distribution1D::new(function.clone());
+        assert_eq!(distribution.len(), 6);
+        assert!((distribution.integral() - 10.0 / 6.0).abs() < 1e-12);
+
+        let n = 100_000;
+        let mut counts = vec![0; function.len()];
+        for i in 0..n {
+            // Evenly spaced values of u give the exact proportions of the pieces
+            let u = (i as f64 + 0.5) / n as f64;
+            let (x, pdf, index) = distribution.sample_continuous(u);
+            assert!((0.0..1.0).contains(&x));
+            assert_eq!((x * 6.0) as usize, index);
+            assert!(function[index] > 0.0);
+            assert!((pdf - function[index] / distribution.integral()).abs() < 1e-12);
+            assert_eq!(pdf, distribution.pdf(x));
+            counts[index] += 1;
+        }
+        for (i, &count) in counts.iter().enumerate() {
+            let expected = function[i] / 10.0;
+            assert!((count as f64 / n as f64 - expected).abs() < 1e-4);
+            assert!((distribution.discrete_pdf(i) - expected).abs() < 1e-12);
+            let (index, probability) = distribution.sample_discrete((i as f64 + 0.5) / 6.0);
+            assert_eq!(probability, distribution.discrete_pdf(index));
+        }
+    }
+
+    #[test]
+    fn zero_distribution_is_uniform() {
+        let distribution = Distribution1D::new(vec![0.0; 4]);
+        assert_eq!(distribution.integral(), 0.0);
+        for i in 0..4 {
+            let u = (i as f64 + 0.25) / 4.0;
+            let (x, pdf, index) = distribution.sample_continuous(u);
+            assert_eq!(index, i);
+            assert!((x - u).abs() < 1e-12);
+            assert!((pdf - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn distribution_2d_pdf_matches_samples() {
+        let (width, height) = (5, 3);
+        let function: Vec<f64> = (0..width * height).map(|i| ((i * 7) % 4) as f64).collect();
+        let distribution = Distribution2D::new(&function, width, height);
+        let total: f64 = function.iter().sum();
+        for i in 0..50 {
+            for j in 0..50 {
+                let u = ((i as f64 + 0.5) / 50.0, (j as f64 + 0.5) / 50.0);
+                let ((x, y), pdf) = distribution.sample_continuous(u);
+                let (column, row) = ((x * width as f64) as usize, (y * height as f64) as usize);
+                let value = function[row * width + column];
+                assert!(value > 0.0);
+                // The density is the function normalized to integrate to one over the square
+                let expected = value / (total / (width * height) as f64);
+                assert!((pdf - expected).abs() < 1e-9);
+                assert!((distribution.pdf((x, y)) - pdf).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_sphere_directions() {
+        for i in 0..20 {
+            for j in 0..20 {
+                let d = uniform_sphere((i as f64 / 20.0, j as f64 / 20.0));
+                assert!((d.norm() - 1.0).abs() < 1e-12);
+            }
+        }
+        assert!((uniform_sphere_pdf() * 4.0 * PI - 1.0).abs() < 1e-15);
+    }
+}
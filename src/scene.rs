@@ -0,0 +1,628 @@
+//! Declarative scene description files
+//!
+//! Scenes are written in TOML. For example:
+//!
+//! ```toml
+//! [camera]
+//! lookfrom = [13.0, 2.0, 3.0]
+//! lookat = [0.0, 0.0, 0.0]
+//! vfov = 20.0
+//! aperture = 0.1
+//! focus_distance = 10.0
+//!
+//! [background]
+//! type = "map"
+//! path = "sky.hdr"
+//! rotation = 90.0
+//!
+//! [materials.ground]
+//! type = "lambertian"
+//! colour = [0.5, 0.5, 0.5]
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0.0, -1000.0, 0.0]
+//! radius = 1000.0
+//! material = "ground"
+//!
+//! [[objects]]
+//! type = "mesh"
+//! path = "teapot.obj"
+//! ```
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::environments::{Black, Constant, Environment, EnvironmentMap, Gradient};
+use crate::loaders::{load_obj_with_libraries, load_obj_with_material};
+use crate::materials::{DiffuseLight, Lambertian, Material, Metal, Transparent};
+use crate::objects::{Capsule, Cone, Cuboid, Cylinder, Disk, Plane, Quad, Sphere, Torus, Triangle};
+use crate::surface::Surface;
+use crate::vec3::{Vec3, VecR3};
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use toml::Spanned;
+
+/// A renderable scene
+pub struct Scene {
+    pub camera: CameraSettings,
+    pub background: Box<dyn Environment>,
+    pub world: Bvh,
+    /// Files read while loading the scene other than the scene description itself,
+    /// such as meshes, material libraries and environment maps
+    pub files: Vec<PathBuf>,
+}
+
+impl Scene {
+    /// Load a scene from a TOML scene description file.
+    ///
+    /// Relative mesh paths are resolved relative to the directory containing the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read scene '{}'", path.display()))?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::parse(&text, directory).map_err(|e| e.into_anyhow(path, &text))
+    }
+
+    /// Parse a scene description, resolving relative paths against `directory`.
+    fn parse(text: &str, directory: &Path) -> Result<Self, SceneError> {
+        let description: SceneDescription = toml::from_str(text).map_err(|e| SceneError {
+            span: e.span(),
+            error: anyhow!("{}", e.message()),
+        })?;
+
+        let materials: HashMap<&str, Arc<dyn Material>> = description
+            .materials
+            .iter()
+            .map(|(name, material)| (name.as_str(), material.build()))
+            .collect();
+        let get_material = |name: &str, span: &Range<usize>| {
+            materials
+                .get(name)
+                .cloned()
+                .ok_or_else(|| SceneError::new(span, anyhow!("unknown material '{}'", name)))
+        };
+
+        let mut surfaces: Vec<Arc<dyn Surface>> = Vec::new();
+        let mut files = Vec::new();
+        for object in &description.objects {
+            let span = object.span();
+            let invalid = |e: anyhow::Error| SceneError::new(&span, e);
+            match object.get_ref() {
+                ObjectDescription::Sphere {
+                    center,
+                    radius,
+                    material,
+                } => surfaces.push(Arc::new(Sphere::new(
+                    Vec3::from(*center),
+                    *radius,
+                    get_material(material, &span)?,
+                ))),
+                ObjectDescription::Triangle { vertices, material } => {
+                    let [a, b, c] = *vertices;
+                    surfaces.push(Arc::new(Triangle::new(
+                        Vec3::from(a),
+                        Vec3::from(b),
+                        Vec3::from(c),
+                        get_material(material, &span)?,
+                    )))
+                }
+                ObjectDescription::Mesh { path, material } => {
+                    let mesh_path = directory.join(path);
+                    let meshes = match material {
+                        Some(name) => {
+                            load_obj_with_material(&mesh_path, get_material(name, &span)?)
+                        }
+                        None => load_obj_with_libraries(&mesh_path).map(|(meshes, libraries)| {
+                            files.extend(libraries);
+                            meshes
+                        }),
+                    }
+                    .map_err(|e| SceneError::new(&span, e))?;
+                    files.push(mesh_path);
+                    for mesh in meshes {
+                        surfaces.push(Arc::new(mesh.mesh));
+                    }
+                }
+                ObjectDescription::Plane {
+                    point,
+                    normal,
+                    material,
+                } => surfaces.push(Arc::new(Plane::new(
+                    Vec3::from(*point),
+                    non_zero("normal", *normal).map_err(invalid)?,
+                    get_material(material, &span)?,
+                ))),
+                ObjectDescription::Quad {
+                    corner,
+                    u,
+                    v,
+                    material,
+                } => {
+                    let (u, v) = (Vec3::from(*u), Vec3::from(*v));
+                    // Also rejects zero edges
+                    if u.cross(v).norm() <= 1e-12 * u.norm() * v.norm() {
+                        return Err(invalid(anyhow!("'u' and 'v' must not be parallel")));
+                    }
+                    surfaces.push(Arc::new(Quad::new(
+                        Vec3::from(*corner),
+                        u,
+                        v,
+                        get_material(material, &span)?,
+                    )))
+                }
+                ObjectDescription::Disk {
+                    center,
+                    normal,
+                    radius,
+                    material,
+                } => surfaces.push(Arc::new(Disk::new(
+                    Vec3::from(*center),
+                    non_zero("normal", *normal).map_err(invalid)?,
+                    positive("radius", *radius).map_err(invalid)?,
+                    get_material(material, &span)?,
+                ))),
+                ObjectDescription::Cuboid { min, max, material } => {
+                    if (0..3).any(|i| min[i] >= max[i]) {
+                        return Err(invalid(anyhow!(
+                            "'min' must be less than 'max' on every axis"
+                        )));
+                    }
+                    surfaces.push(Arc::new(Cuboid::new(
+                        Vec3::from(*min),
+                        Vec3::from(*max),
+                        get_material(material, &span)?,
+                    )))
+                }
+                ObjectDescription::Cylinder {
+                    base,
+                    top,
+                    radius,
+                    caps,
+                    sweep,
+                    material,
+                } => {
+                    distinct(("base", *base), ("top", *top)).map_err(invalid)?;
+                    let mut cylinder = Cylinder::new(
+                        Vec3::from(*base),
+                        Vec3::from(*top),
+                        positive("radius", *radius).map_err(invalid)?,
+                        get_material(material, &span)?,
+                    )
+                    .with_sweep(*sweep);
+                    if *caps {
+                        cylinder = cylinder.with_caps();
+                    }
+                    surfaces.push(Arc::new(cylinder))
+                }
+                ObjectDescription::Cone {
+                    base,
+                    apex,
+                    radius,
+                    caps,
+                    sweep,
+                    material,
+                } => {
+                    distinct(("base", *base), ("apex", *apex)).map_err(invalid)?;
+                    let mut cone = Cone::new(
+                        Vec3::from(*base),
+                        Vec3::from(*apex),
+                        positive("radius", *radius).map_err(invalid)?,
+                        get_material(material, &span)?,
+                    )
+                    .with_sweep(*sweep);
+                    if *caps {
+                        cone = cone.with_caps();
+                    }
+                    surfaces.push(Arc::new(cone))
+                }
+                ObjectDescription::Capsule {
+                    a,
+                    b,
+                    radius,
+                    material,
+                } => {
+                    distinct(("a", *a), ("b", *b)).map_err(invalid)?;
+                    surfaces.push(Arc::new(Capsule::new(
+                        Vec3::from(*a),
+                        Vec3::from(*b),
+                        positive("radius", *radius).map_err(invalid)?,
+                        get_material(material, &span)?,
+                    )))
+                }
+                ObjectDescription::Torus {
+                    center,
+                    axis,
+                    major_radius,
+                    minor_radius,
+                    material,
+                } => surfaces.push(Arc::new(Torus::new(
+                    Vec3::from(*center),
+                    non_zero("axis", *axis).map_err(invalid)?,
+                    positive("major_radius", *major_radius).map_err(invalid)?,
+                    positive("minor_radius", *minor_radius).map_err(invalid)?,
+                    get_material(material, &span)?,
+                ))),
+            }
+        }
+
+        let background: Box<dyn Environment> = match &description.background {
+            Some(background) => {
+                if let BackgroundDescription::Map { path, .. } = background.get_ref() {
+                    files.push(directory.join(path));
+                }
+                background
+                    .get_ref()
+                    .build(directory)
+                    .map_err(|e| SceneError::new(&background.span(), e))?
+            }
+            None => Box::new(Gradient::default()),
+        };
+        Ok(Self {
+            camera: description.camera,
+            background,
+            world: Bvh::new(surfaces),
+            files,
+        })
+    }
+}
+
+/// Check that a vector given in a scene description is not zero.
+fn non_zero(name: &str, value: [f64; 3]) -> anyhow::Result<VecR3> {
+    let vector = Vec3::from(value);
+    if vector.norm() > 0.0 {
+        Ok(vector)
+    } else {
+        Err(anyhow!("'{}' must not be zero", name))
+    }
+}
+
+/// Check that a length given in a scene description is positive.
+fn positive(name: &str, value: f64) -> anyhow::Result<f64> {
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(anyhow!("'{}' must be positive; got {}", name, value))
+    }
+}
+
+/// Check that two points given in a scene description are different.
+///
+/// Each point is given with its name.
+fn distinct(a: (&str, [f64; 3]), b: (&str, [f64; 3])) -> anyhow::Result<()> {
+    if a.1 == b.1 {
+        Err(anyhow!("'{}' and '{}' must be different points", a.0, b.0))
+    } else {
+        Ok(())
+    }
+}
+
+/// An error at a location in a scene description file
+struct SceneError {
+    span: Option<Range<usize>>,
+    error: anyhow::Error,
+}
+
+impl SceneError {
+    fn new(span: &Range<usize>, error: anyhow::Error) -> Self {
+        Self {
+            span: Some(span.clone()),
+            error,
+        }
+    }
+
+    /// Convert to an error message prefixed by the file name, line and column.
+    fn into_anyhow(self, path: &Path, text: &str) -> anyhow::Error {
+        match self.span {
+            Some(span) => {
+                let (line, column) = line_column(text, span.start);
+                self.error
+                    .context(format!("{}:{}:{}", path.display(), line, column))
+            }
+            None => self.error.context(format!("{}", path.display())),
+        }
+    }
+}
+
+/// The 1-based line and column of a byte offset into a string.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// Camera position and lens settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraSettings {
+    /// Where the camera is located.
+    pub lookfrom: [f64; 3],
+    /// Where the camera is looking.
+    pub lookat: [f64; 3],
+    /// Vertical up direction for the camera.
+    #[serde(default = "default_vup")]
+    pub vup: [f64; 3],
+    /// Vertical field of view in degrees.
+    pub vfov: f64,
+    /// Diameter of the aperture; influences focus blur.
+    #[serde(default)]
+    pub aperture: f64,
+    /// Distance at which objects are in focus. Defaults to the distance to `lookat`.
+    pub focus_distance: Option<f64>,
+}
+
+fn default_vup() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+impl CameraSettings {
+    /// Create a camera with these settings for an image of the given aspect ratio.
+    pub fn build(&self, aspect_ratio: f64) -> Camera {
+        let lookfrom = Vec3::from(self.lookfrom);
+        let lookat = Vec3::from(self.lookat);
+        let focus_distance = self
+            .focus_distance
+            .unwrap_or_else(|| (lookfrom - lookat).norm());
+        Camera::new(
+            lookfrom,
+            lookat,
+            Vec3::from(self.vup),
+            self.vfov,
+            aspect_ratio,
+            self.aperture,
+            focus_distance,
+        )
+    }
+}
+
+/// The contents of a scene description file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    camera: CameraSettings,
+    background: Option<Spanned<BackgroundDescription>>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDescription>,
+    #[serde(default)]
+    objects: Vec<Spanned<ObjectDescription>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundDescription {
+    Gradient {
+        bottom: [f64; 3],
+        top: [f64; 3],
+    },
+    #[serde(alias = "solid")]
+    Constant {
+        colour: [f64; 3],
+    },
+    Black,
+    /// An equirectangular `.hdr` or `.exr` image
+    Map {
+        path: String,
+        /// Rotation about the vertical axis in degrees
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+impl BackgroundDescription {
+    fn build(&self, directory: &Path) -> anyhow::Result<Box<dyn Environment>> {
+        Ok(match *self {
+            BackgroundDescription::Gradient { bottom, top } => {
+                Box::new(Gradient::new(Vec3::from(bottom), Vec3::from(top)))
+            }
+            BackgroundDescription::Constant { colour } => {
+                Box::new(Constant::new(Vec3::from(colour)))
+            }
+            BackgroundDescription::Black => Box::new(Black),
+            BackgroundDescription::Map {
+                ref path,
+                rotation,
+                intensity,
+            } => Box::new(
+                EnvironmentMap::load(directory.join(path))?
+                    .with_rotation(rotation)
+                    .with_intensity(intensity),
+            ),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDescription {
+    Lambertian {
+        colour: [f64; 3],
+    },
+    Metal {
+        colour: [f64; 3],
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Transparent {
+        refractive_index: f64,
+    },
+    DiffuseLight {
+        colour: [f64; 3],
+    },
+}
+
+impl MaterialDescription {
+    fn build(&self) -> Arc<dyn Material> {
+        match *self {
+            MaterialDescription::Lambertian { colour } => {
+                Arc::new(Lambertian::new(Vec3::from(colour)))
+            }
+            MaterialDescription::Metal { colour, fuzz } => {
+                Arc::new(Metal::new(Vec3::from(colour), fuzz))
+            }
+            MaterialDescription::Transparent { refractive_index } => {
+                Arc::new(Transparent::new(refractive_index))
+            }
+            MaterialDescription::DiffuseLight { colour } => {
+                Arc::new(DiffuseLight::new(Vec3::from(colour)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        material: String,
+    },
+    /// A Wavefront OBJ mesh; uses the materials from its MTL files unless one is given.
+    Mesh {
+        path: String,
+        material: Option<String>,
+    },
+    /// An infinite plane
+    Plane {
+        point: [f64; 3],
+        normal: [f64; 3],
+        material: String,
+    },
+    /// A parallelogram with edges `u` and `v` from `corner`
+    Quad {
+        corner: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    Disk {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    /// An axis-aligned box between two opposite corners
+    #[serde(alias = "box")]
+    Cuboid {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: String,
+    },
+    /// A cylinder between the centers of its ends; `sweep` is in degrees
+    Cylinder {
+        base: [f64; 3],
+        top: [f64; 3],
+        radius: f64,
+        #[serde(default)]
+        caps: bool,
+        #[serde(default = "default_sweep")]
+        sweep: f64,
+        material: String,
+    },
+    /// A cone from the center of its base to its apex; `sweep` is in degrees
+    Cone {
+        base: [f64; 3],
+        apex: [f64; 3],
+        radius: f64,
+        #[serde(default)]
+        caps: bool,
+        #[serde(default = "default_sweep")]
+        sweep: f64,
+        material: String,
+    },
+    /// All points within `radius` of the segment from `a` to `b`
+    Capsule {
+        a: [f64; 3],
+        b: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Torus {
+        center: [f64; 3],
+        axis: [f64; 3],
+        major_radius: f64,
+        minor_radius: f64,
+        material: String,
+    },
+}
+
+fn default_sweep() -> f64 {
+    360.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a scene with a single object and return the error message.
+    fn object_error(object: &str) -> String {
+        let text = format!(
+            "[camera]\nlookfrom = [0.0, 0.0, 1.0]\nlookat = [0.0, 0.0, 0.0]\nvfov = 40.0\n\n\
+             [materials.grey]\ntype = \"lambertian\"\ncolour = [0.5, 0.5, 0.5]\n\n\
+             [[objects]]\n{}\nmaterial = \"grey\"\n",
+            object
+        );
+        match Scene::parse(&text, Path::new("")) {
+            Ok(_) => panic!("scene should be invalid"),
+            Err(e) => format!("{:#}", e.into_anyhow(Path::new("scene.toml"), &text)),
+        }
+    }
+
+    #[test]
+    fn invalid_flat_objects() {
+        assert_eq!(
+            object_error("type = \"plane\"\npoint = [0.0, 0.0, 0.0]\nnormal = [0.0, 0.0, 0.0]"),
+            "scene.toml:10:1: 'normal' must not be zero"
+        );
+        assert!(object_error(
+            "type = \"disk\"\ncenter = [0.0, 0.0, 0.0]\nnormal = [0.0, 1.0, 0.0]\nradius = -1.0"
+        )
+        .ends_with("'radius' must be positive; got -1"));
+        assert!(object_error(
+            "type = \"quad\"\ncorner = [0.0, 0.0, 0.0]\nu = [1.0, 2.0, 0.0]\nv = [2.0, 4.0, 0.0]"
+        )
+        .ends_with("'u' and 'v' must not be parallel"));
+        assert!(object_error(
+            "type = \"quad\"\ncorner = [0.0, 0.0, 0.0]\nu = [1.0, 0.0, 0.0]\nv = [0.0, 0.0, 0.0]"
+        )
+        .ends_with("'u' and 'v' must not be parallel"));
+        assert!(
+            object_error("type = \"cuboid\"\nmin = [0.0, 0.0, 0.0]\nmax = [1.0, 0.0, 1.0]")
+                .ends_with("'min' must be less than 'max' on every axis")
+        );
+        assert!(
+            object_error("type = \"box\"\nmin = [0.0, 0.0, 0.0]\nmax = [1.0, 1.0, -1.0]")
+                .ends_with("'min' must be less than 'max' on every axis")
+        );
+    }
+
+    #[test]
+    fn invalid_round_objects() {
+        let cylinder =
+            "type = \"cylinder\"\nbase = [0.0, 0.0, 0.0]\ntop = [0.0, 0.0, 0.0]\nradius = 1.0";
+        assert!(object_error(cylinder).ends_with("'base' and 'top' must be different points"));
+        let cone = "type = \"cone\"\nbase = [0.0, 0.0, 0.0]\napex = [0.0, 1.0, 0.0]\nradius = 0.0";
+        assert!(object_error(cone).ends_with("'radius' must be positive; got 0"));
+        let capsule = "type = \"capsule\"\na = [1.0, 0.0, 0.0]\nb = [1.0, 0.0, 0.0]\nradius = 1.0";
+        assert!(object_error(capsule).ends_with("'a' and 'b' must be different points"));
+        let torus = "type = \"torus\"\ncenter = [0.0, 0.0, 0.0]\naxis = [0.0, 1.0, 0.0]\n\
+                     major_radius = 1.0\nminor_radius = -0.25";
+        assert!(object_error(torus).ends_with("'minor_radius' must be positive; got -0.25"));
+    }
+}
@@ -0,0 +1,551 @@
+//! Signed distance functions
+//!
+//! Describe shapes for the `Sdf` surface, which renders them by sphere tracing.
+//! Shapes are centered on the origin and are combined and deformed with the methods of
+//! `DistanceFunction`, for example
+//! `Sphere::new(1.0).smooth_union(Cuboid::new(size).translate(offset), 0.2).onion(0.05)`.
+use crate::aabb::Aabb;
+use crate::vec3::{Vec3, VecR3};
+
+/// A signed distance function
+///
+/// The distance is negative inside the shape.
+/// It may underestimate the distance to the surface but must not overestimate it,
+/// otherwise sphere tracing can step through the surface.
+pub trait DistanceFunction: Sync + Send {
+    /// Signed distance from `p` to the surface.
+    fn distance(&self, p: VecR3) -> f64;
+
+    /// An axis-aligned box containing the shape.
+    ///
+    /// Returns None if the shape is unbounded.
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    /// Move the shape by `offset`.
+    fn translate(self, offset: VecR3) -> Translate<Self>
+    where
+        Self: Sized,
+    {
+        Translate {
+            shape: self,
+            offset,
+        }
+    }
+
+    /// Points inside either shape.
+    fn union<D: DistanceFunction>(self, other: D) -> SmoothUnion<Self, D>
+    where
+        Self: Sized,
+    {
+        self.smooth_union(other, 0.0)
+    }
+
+    /// The union of two shapes with a blend between them.
+    ///
+    /// # Arguments
+    /// * `other` - The other shape.
+    /// * `smoothness` - Distance between the shapes over which they are blended.
+    fn smooth_union<D: DistanceFunction>(self, other: D, smoothness: f64) -> SmoothUnion<Self, D>
+    where
+        Self: Sized,
+    {
+        SmoothUnion {
+            a: self,
+            b: other,
+            smoothness,
+        }
+    }
+
+    /// Repeat the shape forever on a grid.
+    ///
+    /// The shape must fit in one cell of the grid for the distance to remain valid.
+    ///
+    /// # Arguments
+    /// * `period` - Spacing of the copies along each axis; zero to not repeat along an axis.
+    fn repeat(self, period: VecR3) -> Repeat<Self>
+    where
+        Self: Sized,
+    {
+        Repeat {
+            shape: self,
+            period,
+        }
+    }
+
+    /// Twist the shape around the y axis.
+    ///
+    /// The distance is scaled down by how much the twist stretches space at the largest
+    /// radius of the shape, so strong twists of wide shapes are slow to trace.
+    ///
+    /// # Arguments
+    /// * `degrees_per_unit` - Rotation about the axis per unit of height.
+    fn twist(self, degrees_per_unit: f64) -> Twist<Self>
+    where
+        Self: Sized,
+    {
+        let radius = self.bounding_box().map(|bounds| axis_radius(&bounds));
+        Twist {
+            shape: self,
+            rate: degrees_per_unit.to_radians(),
+            radius,
+        }
+    }
+
+    /// Grow the shape by `radius`, rounding its edges.
+    fn round(self, radius: f64) -> Round<Self>
+    where
+        Self: Sized,
+    {
+        Round {
+            shape: self,
+            radius,
+        }
+    }
+
+    /// Hollow out the shape, leaving a shell of `thickness` around its surface.
+    ///
+    /// Can be applied several times to create nested shells.
+    fn onion(self, thickness: f64) -> Onion<Self>
+    where
+        Self: Sized,
+    {
+        Onion {
+            shape: self,
+            thickness,
+        }
+    }
+}
+
+/// Any function of a point is a distance function with no bounds.
+impl<F> DistanceFunction for F
+where
+    F: Fn(VecR3) -> f64 + Sync + Send,
+{
+    fn distance(&self, p: VecR3) -> f64 {
+        self(p)
+    }
+}
+
+/// A sphere centered on the origin
+pub struct Sphere {
+    radius: f64,
+}
+
+impl Sphere {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl DistanceFunction for Sphere {
+    fn distance(&self, p: VecR3) -> f64 {
+        p.norm() - self.radius
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::new(Vec3::new(-r, -r, -r), Vec3::new(r, r, r)))
+    }
+}
+
+/// An axis-aligned box centered on the origin
+pub struct Cuboid {
+    half_size: VecR3,
+}
+
+impl Cuboid {
+    /// Create a box extending from `-half_size` to `half_size`.
+    pub fn new(half_size: VecR3) -> Self {
+        Self { half_size }
+    }
+}
+
+impl DistanceFunction for Cuboid {
+    fn distance(&self, p: VecR3) -> f64 {
+        let q = p.map(f64::abs) - self.half_size;
+        let outside = q.max(Vec3::new(0.0, 0.0, 0.0)).norm();
+        let inside = q.x.max(q.y).max(q.z).min(0.0);
+        outside + inside
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(-self.half_size, self.half_size))
+    }
+}
+
+/// A torus centered on the origin around the y axis
+pub struct Torus {
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl Torus {
+    /// Create a torus
+    ///
+    /// # Arguments
+    /// * `major_radius` - Distance from the center to the middle of the tube.
+    /// * `minor_radius` - Radius of the tube.
+    pub fn new(major_radius: f64, minor_radius: f64) -> Self {
+        Self {
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl DistanceFunction for Torus {
+    fn distance(&self, p: VecR3) -> f64 {
+        let ring = p.x.hypot(p.z) - self.major_radius;
+        ring.hypot(p.y) - self.minor_radius
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let outer = self.major_radius + self.minor_radius;
+        let r = self.minor_radius;
+        Some(Aabb::new(
+            Vec3::new(-outer, -r, -outer),
+            Vec3::new(outer, r, outer),
+        ))
+    }
+}
+
+/// The Mandelbulb fractal, a three dimensional Mandelbrot set
+///
+/// The distance is estimated from the escape of the iterated point,
+/// so it is only accurate close to the fractal.
+pub struct Mandelbulb {
+    power: f64,
+    iterations: u32,
+}
+
+impl Mandelbulb {
+    /// Create a Mandelbulb
+    ///
+    /// # Arguments
+    /// * `power` - Power of the iterated function; 8 gives the usual shape.
+    /// * `iterations` - Maximum number of iterations; more give finer detail.
+    pub fn new(power: f64, iterations: u32) -> Self {
+        Self { power, iterations }
+    }
+}
+
+impl Default for Mandelbulb {
+    fn default() -> Self {
+        Self::new(8.0, 12)
+    }
+}
+
+impl DistanceFunction for Mandelbulb {
+    fn distance(&self, p: VecR3) -> f64 {
+        let mut z = p;
+        // Derivative of the iterated function with respect to p
+        let mut dr = 1.0;
+        let mut r = z.norm();
+        for _ in 0..self.iterations {
+            if r > 2.0 || r == 0.0 {
+                break;
+            }
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let scale = r.powf(self.power);
+            z = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            ) * scale
+                + p;
+            r = z.norm();
+        }
+        if r == 0.0 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / dr
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Every point outside radius 2 escapes; the shape itself stays within about 1.2
+        let r = 1.25;
+        Some(Aabb::new(Vec3::new(-r, -r, -r), Vec3::new(r, r, r)))
+    }
+}
+
+/// A shape moved by an offset; see `DistanceFunction::translate`.
+pub struct Translate<D> {
+    shape: D,
+    offset: VecR3,
+}
+
+impl<D: DistanceFunction> DistanceFunction for Translate<D> {
+    fn distance(&self, p: VecR3) -> f64 {
+        self.shape.distance(p - self.offset)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounds = self.shape.bounding_box()?;
+        Some(Aabb::new(
+            bounds.min + self.offset,
+            bounds.max + self.offset,
+        ))
+    }
+}
+
+/// A blended union of shapes; see `DistanceFunction::smooth_union`.
+pub struct SmoothUnion<A, B> {
+    a: A,
+    b: B,
+    smoothness: f64,
+}
+
+impl<A: DistanceFunction, B: DistanceFunction> DistanceFunction for SmoothUnion<A, B> {
+    fn distance(&self, p: VecR3) -> f64 {
+        let a = self.a.distance(p);
+        let b = self.b.distance(p);
+        let k = self.smoothness;
+        if k <= 0.0 {
+            return a.min(b);
+        }
+        // Polynomial smooth minimum
+        let h = (k - (a - b).abs()).max(0.0) / k;
+        a.min(b) - h * h * k / 4.0
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounds = self.a.bounding_box()?.union(&self.b.bounding_box()?);
+        // The blend lowers the distance by at most a quarter of the smoothness
+        let margin = self.smoothness.max(0.0) / 4.0;
+        Some(expand(&bounds, margin))
+    }
+}
+
+/// An infinitely repeated shape; see `DistanceFunction::repeat`.
+pub struct Repeat<D> {
+    shape: D,
+    period: VecR3,
+}
+
+impl<D: DistanceFunction> DistanceFunction for Repeat<D> {
+    fn distance(&self, p: VecR3) -> f64 {
+        let mut q = p;
+        for axis in 0..3 {
+            let period = self.period[axis];
+            if period > 0.0 {
+                q[axis] -= period * (q[axis] / period).round();
+            }
+        }
+        self.shape.distance(q)
+    }
+}
+
+/// A shape twisted around the y axis; see `DistanceFunction::twist`.
+pub struct Twist<D> {
+    shape: D,
+    /// Rotation in radians per unit of height
+    rate: f64,
+    /// Largest distance of the untwisted shape from the y axis, if it is bounded
+    radius: Option<f64>,
+}
+
+impl<D: DistanceFunction> DistanceFunction for Twist<D> {
+    fn distance(&self, p: VecR3) -> f64 {
+        let (sin, cos) = (-self.rate * p.y).sin_cos();
+        let q = Vec3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z);
+        // Locally the twist is a shear of magnitude a = rate * radius, which stretches
+        // space by up to its largest singular value. The segment from p to the nearest
+        // surface point stays within the larger of their distances from the axis.
+        let radius = p.x.hypot(p.z).max(self.radius.unwrap_or(0.0));
+        let a = self.rate.abs() * radius;
+        let stretch = (a + (a * a + 4.0).sqrt()) / 2.0;
+        self.shape.distance(q) / stretch
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounds = self.shape.bounding_box()?;
+        // Any rotation about the y axis stays within the cylinder around the corners
+        let radius = self.radius?;
+        Some(Aabb::new(
+            Vec3::new(-radius, bounds.min.y, -radius),
+            Vec3::new(radius, bounds.max.y, radius),
+        ))
+    }
+}
+
+/// A shape grown by a radius; see `DistanceFunction::round`.
+pub struct Round<D> {
+    shape: D,
+    radius: f64,
+}
+
+impl<D: DistanceFunction> DistanceFunction for Round<D> {
+    fn distance(&self, p: VecR3) -> f64 {
+        self.shape.distance(p) - self.radius
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(expand(&self.shape.bounding_box()?, self.radius))
+    }
+}
+
+/// A hollow shell around a shape; see `DistanceFunction::onion`.
+pub struct Onion<D> {
+    shape: D,
+    thickness: f64,
+}
+
+impl<D: DistanceFunction> DistanceFunction for Onion<D> {
+    fn distance(&self, p: VecR3) -> f64 {
+        self.shape.distance(p).abs() - self.thickness
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(expand(&self.shape.bounding_box()?, self.thickness))
+    }
+}
+
+/// The largest distance of a point in a box from the y axis
+fn axis_radius(bounds: &Aabb) -> f64 {
+    [bounds.min.x, bounds.max.x]
+        .iter()
+        .flat_map(|&x| [bounds.min.z, bounds.max.z].map(|z| x.hypot(z)))
+        .fold(0.0, f64::max)
+}
+
+/// Grow a box by `margin` on every side.
+fn expand(bounds: &Aabb, margin: f64) -> Aabb {
+    let margin = Vec3::new(margin, margin, margin);
+    Aabb::new(bounds.min - margin, bounds.max + margin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::SampleRng;
+    use rand::{Rng, SeedableRng};
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn cuboid_distance() {
+        let cube = Cuboid::new(Vec3::new(1.0, 2.0, 3.0));
+        assert_close(cube.distance(Vec3::new(0.0, 0.0, 0.0)), -1.0);
+        assert_close(cube.distance(Vec3::new(4.0, 0.0, 0.0)), 3.0);
+        assert_close(cube.distance(Vec3::new(4.0, 6.0, 0.0)), 5.0);
+    }
+
+    #[test]
+    fn smooth_union_blends_below_minimum() {
+        let a = Sphere::new(1.0);
+        let b = Sphere::new(1.0).translate(Vec3::new(2.0, 0.0, 0.0));
+        let p = Vec3::new(1.0, 1.0, 0.0);
+        let hard = a.distance(p).min(b.distance(p));
+        let blend = Sphere::new(1.0)
+            .smooth_union(Sphere::new(1.0).translate(Vec3::new(2.0, 0.0, 0.0)), 0.5)
+            .distance(p);
+        assert_close(blend, hard - 0.5 / 4.0);
+        // Far from where the shapes meet the union is exact
+        let q = Vec3::new(-3.0, 0.0, 0.0);
+        assert_close(
+            Sphere::new(1.0)
+                .smooth_union(Sphere::new(1.0).translate(Vec3::new(2.0, 0.0, 0.0)), 0.5)
+                .distance(q),
+            2.0,
+        );
+    }
+
+    #[test]
+    fn repeat_onion_and_round() {
+        let grid = Sphere::new(0.5).repeat(Vec3::new(2.0, 0.0, 2.0));
+        assert_close(grid.distance(Vec3::new(4.0, 0.0, -6.0)), -0.5);
+        assert_close(grid.distance(Vec3::new(4.0, 3.0, 0.0)), 2.5);
+        let shell = Sphere::new(1.0).onion(0.1);
+        assert_close(shell.distance(Vec3::new(0.0, 0.0, 0.0)), 0.9);
+        assert_close(shell.distance(Vec3::new(1.0, 0.0, 0.0)), -0.1);
+        let rounded = Cuboid::new(Vec3::new(1.0, 1.0, 1.0)).round(0.25);
+        assert_close(rounded.distance(Vec3::new(2.0, 0.0, 0.0)), 0.75);
+        let bounds = rounded.bounding_box().unwrap();
+        assert_close(bounds.max.x, 1.25);
+    }
+
+    /// A thin slab tilted about the x axis, off to the side of the y axis
+    struct TiltedSlab {
+        /// Unit normal of the slab in the y-z plane
+        normal: VecR3,
+        half_thickness: f64,
+    }
+
+    impl DistanceFunction for TiltedSlab {
+        fn distance(&self, p: VecR3) -> f64 {
+            let slab = p.dot(self.normal).abs() - self.half_thickness;
+            let sides = (p.x - 1.0).abs() - 0.5;
+            let ends = p.y.abs().max(p.z.abs()) - 1.0;
+            slab.max(sides).max(ends)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            Some(Aabb::new(
+                Vec3::new(0.5, -1.0, -1.0),
+                Vec3::new(1.5, 1.0, 1.0),
+            ))
+        }
+    }
+
+    #[test]
+    fn twist_underestimates_distance() {
+        // The twist shears this slab in the direction that it stretches space the most
+        let angle: f64 = 2.2;
+        let normal = Vec3::new(0.0, angle.cos(), angle.sin());
+        let rate: f64 = 1.0;
+        let shape = TiltedSlab {
+            normal,
+            half_thickness: 0.01,
+        }
+        .twist(rate.to_degrees());
+
+        // Points on the faces of the untwisted slab, twisted into place
+        let step = 0.005;
+        let along = Vec3::new(0.0, -normal.z, normal.y);
+        let mut surface = Vec::new();
+        for i in 0..=200 {
+            for j in 0..=400 {
+                for side in [-0.01, 0.01] {
+                    let s = Vec3::new(0.5 + i as f64 * step, 0.0, 0.0)
+                        + along * (-1.0 + j as f64 * step)
+                        + normal * side;
+                    let (sin, cos) = (rate * s.y).sin_cos();
+                    surface.push(Vec3::new(cos * s.x - sin * s.z, s.y, sin * s.x + cos * s.z));
+                }
+            }
+        }
+
+        // Compare with brute force distances from points near the middle of the slab.
+        // The brute force distance ignores the narrow ends of the slab, so it may be larger
+        // than the true distance but not smaller than the estimate.
+        let mut rng = SampleRng::seed_from_u64(1);
+        for _ in 0..200 {
+            let p = Vec3::new(
+                rng.gen_range(0.6..0.9),
+                rng.gen_range(-0.4..0.4),
+                rng.gen_range(-0.4..0.4),
+            );
+            let estimate = shape.distance(p);
+            if estimate <= 0.0 {
+                continue;
+            }
+            let distance = surface
+                .iter()
+                .map(|&s| (p - s).norm())
+                .fold(f64::INFINITY, f64::min);
+            assert!(
+                estimate <= distance,
+                "estimate {} exceeds distance {} at {:?}",
+                estimate,
+                distance,
+                p
+            );
+        }
+    }
+}
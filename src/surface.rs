@@ -1,7 +1,11 @@
+use crate::aabb::Aabb;
 use crate::colour::Colour;
+use crate::lights::LightSample;
 use crate::materials::Material;
 use crate::ray::RayR3;
+use crate::samplers::Sampler;
 use crate::vec3::VecR3;
+use std::sync::Arc;
 
 /// Details about a ray-surface intersection
 pub struct Intersection<'a> {
@@ -9,13 +13,20 @@ pub struct Intersection<'a> {
     pub t: f64,
     /// The intersection point
     pub point: VecR3,
-    /// The unit normal surface vector at the intersection point
+    /// The unit geometric normal surface vector at the intersection point
     /// Points from the side of the surface that the ray enters.
     pub normal: VecR3,
+    /// The unit normal used for shading, such as one interpolated across a mesh face
+    /// Equal to `normal` unless set, and on the same side of the surface as `normal`.
+    pub shading_normal: VecR3,
     /// Whether the ray enters the front face or the back face of the surface.
     pub front_face: bool,
+    /// Surface texture coordinates at the intersection point
+    pub uv: (f64, f64),
     /// Surface material
     pub material: &'a dyn Material,
+    /// The light source that was hit, if the surface is emissive and supports sampling.
+    pub light: Option<&'a dyn Surface>,
 }
 
 impl<'a> Intersection<'a> {
@@ -37,15 +48,65 @@ impl<'a> Intersection<'a> {
             t,
             point,
             normal,
+            shading_normal: normal,
             front_face,
+            uv: (0.0, 0.0),
             material,
+            light: None,
         }
     }
 
+    /// Set the surface texture coordinates.
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.uv = (u, v);
+        self
+    }
+
+    /// Set a shading normal pointing from the front face.
+    ///
+    /// The shading normal is oriented to the same side as the geometric normal,
+    /// which is kept for deciding which side of the surface a direction is on.
+    pub fn with_shading_normal(mut self, front_normal: VecR3) -> Self {
+        let normal = front_normal.as_unit();
+        self.shading_normal = if self.front_face { normal } else { -normal };
+        self
+    }
+
+    /// Record the light source that was hit.
+    pub fn with_light(mut self, light: &'a dyn Surface) -> Self {
+        self.light = Some(light);
+        self
+    }
+
+    /// Light emitted from this intersection
+    pub fn emitted(&self) -> Colour {
+        self.material.emitted(&self.point, self.front_face)
+    }
+
     /// Scatter a ray off of this intersection
-    pub fn scatter(&self, ray: &RayR3) -> Option<(Colour, RayR3)> {
-        self.material
-            .scatter(ray, &self.point, &self.normal, self.front_face)
+    ///
+    /// Rays that the shading normal sends through the geometric surface are absorbed.
+    pub fn scatter(&self, ray: &RayR3, sampler: &mut dyn Sampler) -> Option<(Colour, RayR3)> {
+        let (attenuation, scattered) = self.material.scatter(
+            ray,
+            &self.point,
+            &self.shading_normal,
+            self.front_face,
+            sampler,
+        )?;
+        if self.same_side(&scattered.direction) {
+            Some((attenuation, scattered))
+        } else {
+            None
+        }
+    }
+
+    /// Whether a direction is on the same side of the surface by both the geometric and the
+    /// shading normal.
+    pub fn same_side(&self, direction: &VecR3) -> bool {
+        let geometric = direction.dot(self.normal) > 0.0;
+        let shading = direction.dot(self.shading_normal) > 0.0;
+        geometric == shading
     }
 }
 
@@ -56,13 +117,94 @@ pub trait Surface: Sync + Send {
     /// Intersect a ray with the surface.
     ///
     /// Returns the first intersection that occurs between t_min and t_max.
-    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection>;
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>>;
+
+    /// An axis-aligned box containing the entire surface.
+    ///
+    /// Returns None if the surface is unbounded.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Whether the surface is emissive and supports `sample` and `pdf`.
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    /// The light sources contained in a composite surface.
+    ///
+    /// Only includes surfaces for which `is_light` is true.
+    fn lights(&self) -> Vec<Arc<dyn Surface>> {
+        Vec::new()
+    }
+
+    /// Sample a direction from `origin` towards a point on the surface.
+    ///
+    /// # Arguments
+    /// * `origin` - The point from which the surface is seen.
+    /// * `u` - Uniform random numbers in [0, 1).
+    ///
+    /// # Returns
+    /// None if the surface does not support sampling or cannot be sampled from `origin`.
+    fn sample(&self, _origin: &VecR3, _u: (f64, f64)) -> Option<LightSample> {
+        None
+    }
+
+    /// Probability density with respect to solid angle of `sample` producing a direction.
+    fn pdf(&self, _origin: &VecR3, _direction: &VecR3) -> f64 {
+        0.0
+    }
 }
 
 /// A list of surfaces is itself a surface
-impl Surface for Vec<Box<dyn Surface>> {
-    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection> {
-        return intersect_surfaces(self.iter(), ray, t_min, t_max);
+///
+/// The surfaces are shared so that those that are lights can be sampled individually.
+impl Surface for Vec<Arc<dyn Surface>> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        intersect_surfaces(self.iter(), ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.iter().try_fold(Aabb::empty(), |acc, surface| {
+            surface.bounding_box().map(|aabb| acc.union(&aabb))
+        })
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Surface>> {
+        let mut lights = Vec::new();
+        for surface in self {
+            if surface.is_light() {
+                lights.push(Arc::clone(surface));
+            } else {
+                lights.extend(surface.lights());
+            }
+        }
+        lights
+    }
+}
+
+/// Shared surfaces, such as geometry placed several times with `Transformed`
+impl Surface for Arc<dyn Surface> {
+    fn intersect(&self, ray: &RayR3, t_min: f64, t_max: f64) -> Option<Intersection<'_>> {
+        (**self).intersect(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn is_light(&self) -> bool {
+        (**self).is_light()
+    }
+
+    fn lights(&self) -> Vec<Arc<dyn Surface>> {
+        (**self).lights()
+    }
+
+    fn sample(&self, origin: &VecR3, u: (f64, f64)) -> Option<LightSample> {
+        (**self).sample(origin, u)
+    }
+
+    fn pdf(&self, origin: &VecR3, direction: &VecR3) -> f64 {
+        (**self).pdf(origin, direction)
     }
 }
 
@@ -74,7 +216,7 @@ pub fn intersect_surfaces<'a, I>(
     t_max: f64,
 ) -> Option<Intersection<'a>>
 where
-    I: Iterator<Item = &'a Box<dyn Surface>>,
+    I: Iterator<Item = &'a Arc<dyn Surface>>,
 {
     let mut result = None;
     let mut closest_so_far = t_max;
@@ -86,3 +228,30 @@ where
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::{DiffuseLight, Lambertian};
+    use crate::objects::Sphere;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn list_lights_include_direct_children() {
+        let light: Arc<dyn Surface> = Arc::new(Sphere::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            DiffuseLight::new(Colour::new(1.0, 1.0, 1.0)),
+        ));
+        let ball: Arc<dyn Surface> = Arc::new(Sphere::new(
+            Vec3::new(3.0, 0.0, 0.0),
+            1.0,
+            Lambertian::new(Colour::new(0.5, 0.5, 0.5)),
+        ));
+        let nested: Arc<dyn Surface> = Arc::new(vec![Arc::clone(&light)]);
+        let list: Vec<Arc<dyn Surface>> = vec![Arc::clone(&light), ball, nested];
+        let lights = list.lights();
+        assert_eq!(lights.len(), 2);
+        assert!(lights.iter().all(|l| Arc::ptr_eq(l, &light)));
+    }
+}
@@ -1,64 +1,322 @@
-use std::sync::mpsc;
-use std::sync::{Arc, Mutex};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, PoisonError};
 use std::thread;
 
-/// Apply a function to elements of an iterator in parallel
+/// Applies functions to the items of iterators on a fixed number of worker threads
 ///
-/// Consumes the entire iterator immediately.
-/// The resulting iterator yields elements in an arbitrary order.
-///
-/// # Arguments
-/// * `iter` - Iterator over elements to map. All order is lost.
-/// * `f` - The function to apply at each element.
-/// * `num_workers` - Uses a thread pool with this many worker threads.
-///
-pub fn map<I, F, Y>(iter: I, f: F, num_workers: usize) -> RecvIterator<(<I as Iterator>::Item, Y)>
-where
-    I: Iterator,
-    <I as Iterator>::Item: Send + Clone + 'static,
-    F: Fn(<I as Iterator>::Item) -> Y + Clone + Send + 'static,
-    Y: Send + 'static,
-{
-    let (x_sender, x_receiver) = mpsc::channel::<Option<<I as Iterator>::Item>>();
-    let x_receiver = Arc::new(Mutex::new(x_receiver));
-    let (y_sender, y_receiver) = mpsc::channel();
-
-    for _ in 0..num_workers {
-        let f = f.clone();
-        let rx = Arc::clone(&x_receiver);
-        let tx = y_sender.clone();
-        let worker = move || loop {
-            let x = match rx.lock().unwrap().recv().unwrap() {
-                Some(x) => x,
-                None => break,
-            };
-            tx.send((x.clone(), f(x))).unwrap();
-        };
-        thread::spawn(worker);
+/// The threads only live for the duration of each call so the function, the items and
+/// the results may borrow from the caller.
+/// Items are taken from the iterator as they are needed: at most `queue_size` items are
+/// waiting for a worker, being processed or waiting to be consumed at any time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadPool {
+    num_workers: usize,
+    queue_size: usize,
+}
+
+impl ThreadPool {
+    /// Create a pool with a queue of twice as many items as workers.
+    ///
+    /// # Arguments
+    /// * `num_workers` - Number of worker threads; at least one is used.
+    pub fn new(num_workers: usize) -> Self {
+        let num_workers = num_workers.max(1);
+        Self {
+            num_workers,
+            queue_size: 2 * num_workers,
+        }
+    }
+
+    /// Set the maximum number of items taken from the iterator but not yet consumed.
+    ///
+    /// Values smaller than the number of workers leave some workers idle.
+    pub fn with_queue_size(self, queue_size: usize) -> Self {
+        Self {
+            queue_size: queue_size.max(1),
+            ..self
+        }
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.num_workers
+    }
+
+    /// Apply a function to elements of an iterator in parallel and collect the results.
+    ///
+    /// # Returns
+    /// The results in the order of the iterator, or an error if `f` panicked.
+    pub fn map<I, F, Y>(&self, iter: I, f: F) -> Result<Vec<Y>, WorkerPanic>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) -> Y + Sync,
+        Y: Send,
+    {
+        let mut results = Vec::new();
+        self.map_ordered(iter, f, |y| results.push(y))?;
+        Ok(results)
+    }
+
+    /// Apply a function to elements of an iterator in parallel, consuming the results in order.
+    ///
+    /// # Arguments
+    /// * `iter` - Iterator over elements to map.
+    /// * `f` - The function to apply at each element; called on the worker threads.
+    /// * `consume` - Called on the calling thread with each result in the order of `iter`.
+    ///
+    /// # Returns
+    /// An error if `f` panicked. Elements that had not been started are then skipped and
+    /// the results that were not yet consumed are dropped.
+    pub fn map_ordered<I, F, Y, C>(&self, iter: I, f: F, consume: C) -> Result<(), WorkerPanic>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) -> Y + Sync,
+        Y: Send,
+        C: FnMut(Y),
+    {
+        self.run(iter, f, true, consume)
+    }
+
+    /// Apply a function to elements of an iterator in parallel, consuming results as they finish.
+    ///
+    /// Like `map_ordered` except that results are consumed in an arbitrary order.
+    pub fn map_unordered<I, F, Y, C>(&self, iter: I, f: F, consume: C) -> Result<(), WorkerPanic>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) -> Y + Sync,
+        Y: Send,
+        C: FnMut(Y),
+    {
+        self.run(iter, f, false, consume)
+    }
+
+    fn run<I, F, Y, C>(
+        &self,
+        iter: I,
+        f: F,
+        ordered: bool,
+        mut consume: C,
+    ) -> Result<(), WorkerPanic>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) -> Y + Sync,
+        Y: Send,
+        C: FnMut(Y),
+    {
+        let (x_sender, x_receiver) = mpsc::channel::<(usize, I::Item)>();
+        let x_receiver = Mutex::new(x_receiver);
+        let (y_sender, y_receiver) = mpsc::channel::<(usize, thread::Result<Y>)>();
+        // Set once a worker panics so that the remaining elements are skipped
+        let abort = AtomicBool::new(false);
+
+        thread::scope(|scope| {
+            for _ in 0..self.num_workers {
+                let (f, rx, tx, abort) = (&f, &x_receiver, y_sender.clone(), &abort);
+                scope.spawn(move || loop {
+                    // The receiver is only locked while waiting for the next element.
+                    // Nothing can panic while it is locked so it is never poisoned.
+                    let received = rx.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                    // The sender is dropped once there are no more elements
+                    let (index, x) = match received {
+                        Ok(received) => received,
+                        Err(_) => break,
+                    };
+                    if abort.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    let y = panic::catch_unwind(AssertUnwindSafe(|| f(x)));
+                    if y.is_err() {
+                        abort.store(true, Ordering::Relaxed);
+                    }
+                    if tx.send((index, y)).is_err() {
+                        break;
+                    }
+                });
+            }
+            // Moved into the scope so that the workers stop when it returns
+            let x_sender = x_sender;
+            drop(y_sender);
+
+            let mut xs = iter.into_iter().enumerate();
+            let mut exhausted = false;
+            // Number of elements sent to the workers whose results have not been consumed
+            let mut in_flight = 0;
+            // Results that finished before some earlier result, if ordered
+            let mut pending = BTreeMap::new();
+            let mut next_index = 0;
+            loop {
+                while !exhausted && in_flight < self.queue_size {
+                    match xs.next() {
+                        Some(x) => {
+                            // The workers hold the receiver until the sender is dropped
+                            if x_sender.send(x).is_err() {
+                                return Err(WorkerPanic::disconnected());
+                            }
+                            in_flight += 1;
+                        }
+                        None => exhausted = true,
+                    }
+                }
+                if in_flight == 0 {
+                    return Ok(());
+                }
+                let (index, y) = match y_receiver.recv() {
+                    Ok(result) => result,
+                    Err(_) => return Err(WorkerPanic::disconnected()),
+                };
+                let y = y.map_err(WorkerPanic::new)?;
+                if !ordered {
+                    in_flight -= 1;
+                    consume(y);
+                    continue;
+                }
+                pending.insert(index, y);
+                while let Some(y) = pending.remove(&next_index) {
+                    in_flight -= 1;
+                    next_index += 1;
+                    consume(y);
+                }
+            }
+        })
     }
+}
+
+/// The error returned when a function applied by a `ThreadPool` panics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerPanic {
+    message: String,
+}
 
-    for x in iter {
-        x_sender.send(Some(x)).unwrap();
+impl WorkerPanic {
+    fn new(payload: Box<dyn Any + Send>) -> Self {
+        let message = if let Some(s) = payload.downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "unknown panic payload".to_string()
+        };
+        Self { message }
     }
-    // Send None at the end to shut down the workers
-    for _ in 0..num_workers {
-        x_sender.send(None).unwrap();
+
+    /// Only possible if a worker thread stopped without catching a panic
+    fn disconnected() -> Self {
+        Self {
+            message: "worker threads stopped unexpectedly".to_string(),
+        }
     }
 
-    RecvIterator {
-        receiver: y_receiver,
+    /// The message that the worker panicked with
+    pub fn message(&self) -> &str {
+        &self.message
     }
 }
 
-/// Iterate over values received from a channel.
-pub struct RecvIterator<T> {
-    receiver: mpsc::Receiver<T>,
+impl fmt::Display for WorkerPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "worker thread panicked: {}", self.message)
+    }
 }
 
-impl<T> Iterator for RecvIterator<T> {
-    type Item = T;
+impl Error for WorkerPanic {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    #[test]
+    fn panic_is_returned_as_error() {
+        let pool = ThreadPool::new(4);
+        let result = pool.map(0..100, |x| {
+            if x == 5 {
+                panic!("boom");
+            }
+            x
+        });
+        assert_eq!(result.unwrap_err().message(), "boom");
+        // The pool can still be used
+        let doubled = pool.map(0..10, |x| 2 * x).unwrap();
+        assert_eq!(doubled, (0..10).map(|x| 2 * x).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ordered_results_follow_input() {
+        let pool = ThreadPool::new(4);
+        let mut results = Vec::new();
+        pool.map_ordered(
+            0..16u64,
+            |x| {
+                // Earlier elements finish later
+                thread::sleep(Duration::from_millis(16 - x));
+                x
+            },
+            |y| results.push(y),
+        )
+        .unwrap();
+        assert_eq!(results, (0..16).collect::<Vec<_>>());
+
+        let mut unordered = Vec::new();
+        pool.map_unordered(0..16, |x| x, |y| unordered.push(y))
+            .unwrap();
+        unordered.sort_unstable();
+        assert_eq!(unordered, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn jobs_borrow_local_data() {
+        let pool = ThreadPool::new(3);
+        let data: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let lengths = pool.map(&data, |s| s.len()).unwrap();
+        assert_eq!(lengths, data.iter().map(String::len).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn queue_is_bounded() {
+        let pool = ThreadPool::new(3);
+        // Elements taken from the iterator but not yet consumed
+        let in_flight = Cell::new(0);
+        let max_in_flight = Cell::new(0);
+        let xs = (0..50).inspect(|_| {
+            in_flight.set(in_flight.get() + 1);
+            max_in_flight.set(max_in_flight.get().max(in_flight.get()));
+        });
+        pool.map_ordered(
+            xs,
+            |x| {
+                thread::sleep(Duration::from_millis(1));
+                x
+            },
+            |_| in_flight.set(in_flight.get() - 1),
+        )
+        .unwrap();
+        assert_eq!(in_flight.get(), 0);
+        assert!(
+            max_in_flight.get() <= 6,
+            "{} in flight",
+            max_in_flight.get()
+        );
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.receiver.recv().ok()
+        let pool = pool.with_queue_size(1);
+        max_in_flight.set(0);
+        pool.map_unordered(
+            (0..10).inspect(|_| {
+                in_flight.set(in_flight.get() + 1);
+                max_in_flight.set(max_in_flight.get().max(in_flight.get()));
+            }),
+            |x| x,
+            |_| in_flight.set(in_flight.get() - 1),
+        )
+        .unwrap();
+        assert_eq!(max_in_flight.get(), 1);
     }
 }
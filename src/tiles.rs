@@ -0,0 +1,201 @@
+use anyhow::anyhow;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// A rectangle of pixels rendered as one unit of work
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tile {
+    /// Columns covered by the tile
+    pub xs: Range<u32>,
+    /// Rows covered by the tile
+    pub ys: Range<u32>,
+}
+
+impl Tile {
+    /// Number of pixels in the tile
+    pub fn area(&self) -> u64 {
+        self.xs.len() as u64 * self.ys.len() as u64
+    }
+}
+
+/// The order in which the tiles of an image are rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileOrder {
+    /// Left to right, then top to bottom.
+    Scanline,
+    /// Outwards from the centre of the image.
+    Spiral,
+    /// Along a Hilbert curve, which keeps consecutive tiles close together.
+    Hilbert,
+}
+
+impl FromStr for TileOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scanline" => Ok(TileOrder::Scanline),
+            "spiral" => Ok(TileOrder::Spiral),
+            "hilbert" => Ok(TileOrder::Hilbert),
+            _ => Err(anyhow!(
+                "unknown tile order '{}'; expected scanline, spiral or hilbert",
+                s
+            )),
+        }
+    }
+}
+
+/// Divide an image into tiles.
+///
+/// # Arguments
+/// * `width` - Image width in pixels.
+/// * `height` - Image height in pixels.
+/// * `tile_size` - Width and height of the tiles. Tiles on the right and bottom edges
+///   are smaller if the image size is not a multiple of the tile size.
+/// * `order` - The order of the returned tiles.
+pub fn tiles(width: u32, height: u32, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let columns = width.div_ceil(tile_size);
+    let rows = height.div_ceil(tile_size);
+    let positions = match order {
+        TileOrder::Scanline => (0..rows)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .collect(),
+        TileOrder::Spiral => spiral(columns, rows),
+        TileOrder::Hilbert => hilbert(columns, rows),
+    };
+    positions
+        .into_iter()
+        .map(|(i, j)| Tile {
+            xs: i * tile_size..((i + 1) * tile_size).min(width),
+            ys: j * tile_size..((j + 1) * tile_size).min(height),
+        })
+        .collect()
+}
+
+/// Grid positions in a spiral starting from the centre of a grid
+fn spiral(columns: u32, rows: u32) -> Vec<(u32, u32)> {
+    let total = columns as usize * rows as usize;
+    let mut positions = Vec::with_capacity(total);
+    let (mut i, mut j) = ((columns as i64 - 1) / 2, (rows as i64 - 1) / 2);
+    // Right, down, left, up
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut direction = 0;
+    let mut run = 1;
+    while positions.len() < total {
+        // Runs grow by one every second turn: 1, 1, 2, 2, 3, 3, ...
+        for _ in 0..2 {
+            let (di, dj) = directions[direction];
+            for _ in 0..run {
+                if 0 <= i && i < columns as i64 && 0 <= j && j < rows as i64 {
+                    positions.push((i as u32, j as u32));
+                }
+                i += di;
+                j += dj;
+            }
+            direction = (direction + 1) % 4;
+        }
+        run += 1;
+    }
+    positions
+}
+
+/// Grid positions along a Hilbert curve, skipping those outside the grid
+fn hilbert(columns: u32, rows: u32) -> Vec<(u32, u32)> {
+    let n = columns.max(rows).next_power_of_two() as u64;
+    (0..n * n)
+        .map(|d| hilbert_position(n, d))
+        .filter(|&(i, j)| i < columns && j < rows)
+        .collect()
+}
+
+/// Position of the point at distance `d` along a Hilbert curve filling an `n` by `n` grid
+///
+/// `n` must be a power of two.
+fn hilbert_position(n: u64, d: u64) -> (u32, u32) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = d;
+    let mut s = 1;
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        // Rotate the quadrant
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x as u32, y as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_visit_every_position_once() {
+        let grids = [
+            (1, 1),
+            (4, 4),
+            (8, 8),
+            (5, 3),
+            (3, 7),
+            (6, 6),
+            (1, 9),
+            (9, 1),
+        ];
+        for &(columns, rows) in &grids {
+            for (name, order) in [
+                ("spiral", spiral as fn(u32, u32) -> Vec<(u32, u32)>),
+                ("hilbert", hilbert),
+            ] {
+                let mut positions = order(columns, rows);
+                assert_eq!(
+                    positions.len(),
+                    (columns * rows) as usize,
+                    "{} on {} x {}",
+                    name,
+                    columns,
+                    rows
+                );
+                positions.sort_unstable_by_key(|&(i, j)| (j, i));
+                let expected: Vec<_> = (0..rows)
+                    .flat_map(|j| (0..columns).map(move |i| (i, j)))
+                    .collect();
+                assert_eq!(positions, expected, "{} on {} x {}", name, columns, rows);
+            }
+        }
+    }
+
+    #[test]
+    fn hilbert_steps_between_neighbours() {
+        let positions = hilbert(8, 8);
+        for pair in positions.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert_eq!(a.0.abs_diff(b.0) + a.1.abs_diff(b.1), 1);
+        }
+    }
+
+    #[test]
+    fn tiles_cover_every_pixel_once() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let (width, height) = (70, 45);
+            let mut covered = vec![0; (width * height) as usize];
+            for tile in tiles(width, height, 16, order) {
+                for y in tile.ys.clone() {
+                    for x in tile.xs.clone() {
+                        covered[(y * width + x) as usize] += 1;
+                    }
+                }
+            }
+            assert!(covered.iter().all(|&n| n == 1), "{:?}", order);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::colour::Colour;
+use crate::vec3::Vec3;
+use anyhow::{anyhow, Context};
+use std::str::FromStr;
+
+/// Converts linear scene radiance into display colours
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapping {
+    /// Exposure adjustment in stops; each stop doubles the brightness.
+    pub exposure: f64,
+    pub operator: ToneOperator,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            operator: ToneOperator::Clamp,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn new(exposure: f64, operator: ToneOperator) -> Self {
+        Self { exposure, operator }
+    }
+
+    /// Map a linear scene colour to a linear display colour with components in [0, 1].
+    pub fn apply(&self, colour: Colour) -> Colour {
+        let colour = colour * 2f64.powf(self.exposure);
+        // Negative or NaN values can only come from numerical problems
+        let colour = colour.map(|c| if c > 0.0 { c } else { 0.0 });
+        self.operator.apply(colour).map(|c| c.clamp(0.0, 1.0))
+    }
+
+    /// Map a linear scene colour to an sRGB-encoded display colour with components in [0, 1].
+    pub fn apply_srgb(&self, colour: Colour) -> Colour {
+        self.apply(colour).map(srgb_encode)
+    }
+}
+
+/// A tone mapping curve that compresses high dynamic range colours into [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneOperator {
+    /// Clip each channel to [0, 1].
+    Clamp,
+    /// `x / (1 + x)` in each channel.
+    Reinhard,
+    /// Reinhard's operator rescaled so that `white` maps to 1.
+    ExtendedReinhard { white: f64 },
+    /// A fit of the ACES filmic reference rendering and sRGB output transforms.
+    Aces,
+    /// A log-encoded sigmoid in a desaturating colour space in the style of AgX,
+    /// which gracefully desaturates very bright colours towards white.
+    Agx,
+}
+
+impl ToneOperator {
+    /// Apply the curve to a non-negative linear colour.
+    pub fn apply(&self, colour: Colour) -> Colour {
+        match *self {
+            ToneOperator::Clamp => colour,
+            ToneOperator::Reinhard => colour.map(|c| c / (1.0 + c)),
+            ToneOperator::ExtendedReinhard { white } => {
+                let inv_white_squared = (white * white).recip();
+                colour.map(|c| c * (1.0 + c * inv_white_squared) / (1.0 + c))
+            }
+            ToneOperator::Aces => aces(colour),
+            ToneOperator::Agx => agx(colour),
+        }
+    }
+}
+
+impl FromStr for ToneOperator {
+    type Err = anyhow::Error;
+
+    /// Parse "clamp", "reinhard", "reinhard-extended[:WHITE]", "aces" or "agx".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("clamp"), None) => Ok(ToneOperator::Clamp),
+            (Some("reinhard"), None) => Ok(ToneOperator::Reinhard),
+            (Some("reinhard-extended"), white) => {
+                let white: f64 = match white {
+                    Some(w) => w
+                        .parse()
+                        .with_context(|| format!("invalid white point '{}'", w))?,
+                    None => 4.0,
+                };
+                if !(white.is_finite() && white > 0.0) {
+                    return Err(anyhow!(
+                        "white point must be positive and finite, not {}",
+                        white
+                    ));
+                }
+                Ok(ToneOperator::ExtendedReinhard { white })
+            }
+            (Some("aces"), None) => Ok(ToneOperator::Aces),
+            (Some("agx"), None) => Ok(ToneOperator::Agx),
+            _ => Err(anyhow!(
+                "unknown tone operator '{}'; expected clamp, reinhard, \
+                 reinhard-extended[:WHITE], aces or agx",
+                s
+            )),
+        }
+    }
+}
+
+/// The sRGB transfer function from linear [0, 1] to encoded [0, 1]
+pub fn srgb_encode(x: f64) -> f64 {
+    if x <= 0.003_130_8 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Multiply a colour by a 3x3 matrix given as rows.
+fn transform(m: &[[f64; 3]; 3], c: Colour) -> Colour {
+    let row = |r: &[f64; 3]| r[0] * c.x + r[1] * c.y + r[2] * c.z;
+    Vec3::new(row(&m[0]), row(&m[1]), row(&m[2]))
+}
+
+/// Stephen Hill's fit of the ACES RRT and sRGB ODT
+fn aces(colour: Colour) -> Colour {
+    // sRGB => ACES reference input with the RRT saturation
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT saturation => sRGB
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let v = transform(&INPUT, colour).map(|v| {
+        (v * (v + 0.024_578_6) - 0.000_090_537) / (v * (0.983_729 * v + 0.432_951) + 0.238_081)
+    });
+    transform(&OUTPUT, v)
+}
+
+/// A minimal AgX-style transform
+///
+/// Colours are moved into a slightly desaturated working space, log-encoded over a fixed
+/// range of stops, shaped with a sigmoid contrast curve and converted back.
+fn agx(colour: Colour) -> Colour {
+    const INSET: [[f64; 3]; 3] = [
+        [
+            0.842_479_062_253_094,
+            0.078_433_599_999_999_2,
+            0.079_223_745_147_764_3,
+        ],
+        [
+            0.042_328_242_261_012_3,
+            0.878_468_636_469_772,
+            0.079_166_127_460_543_4,
+        ],
+        [0.042_375_654_905_705_1, 0.078_433_6, 0.879_142_973_793_104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [
+            1.196_879_005_120_17,
+            -0.098_020_881_140_136_8,
+            -0.099_029_744_079_720_5,
+        ],
+        [
+            -0.052_896_851_757_456_2,
+            1.151_903_129_904_17,
+            -0.098_961_176_844_843_3,
+        ],
+        [
+            -0.052_971_635_514_443_8,
+            -0.098_043_450_117_124_1,
+            1.151_073_672_641_16,
+        ],
+    ];
+    const MIN_EV: f64 = -12.473_93;
+    const MAX_EV: f64 = 4.026_069;
+
+    let encoded = transform(&INSET, colour).map(|v| {
+        let x = (v.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        // Polynomial fit of the default AgX contrast sigmoid
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.002_32
+    });
+    // The sigmoid produces display-encoded values; return to linear
+    transform(&OUTSET, encoded).map(|v| v.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [ToneOperator; 5] = [
+        ToneOperator::Clamp,
+        ToneOperator::Reinhard,
+        ToneOperator::ExtendedReinhard { white: 4.0 },
+        ToneOperator::Aces,
+        ToneOperator::Agx,
+    ];
+
+    #[test]
+    fn srgb_breakpoint() {
+        let x = 0.003_130_8;
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-12);
+        assert!((srgb_encode(x) - 12.92 * x).abs() < 1e-15);
+        // Both pieces agree at the breakpoint to within the precision of the standard
+        let power = 1.055 * x.powf(1.0 / 2.4) - 0.055;
+        assert!((srgb_encode(x) - power).abs() < 1e-7);
+        assert!((srgb_encode(x * 1.000_001) - power).abs() < 1e-7);
+        assert!(srgb_encode(x * 0.999) < srgb_encode(x));
+        assert!(srgb_encode(x * 1.001) > srgb_encode(x));
+    }
+
+    #[test]
+    fn operators_are_monotonic_and_bounded() {
+        for &operator in &OPERATORS {
+            let tone_mapping = ToneMapping::new(0.0, operator);
+            assert_eq!(
+                tone_mapping.apply(Colour::new(0.0, 0.0, 0.0)),
+                Colour::new(0.0, 0.0, 0.0),
+                "{:?}",
+                operator
+            );
+            let mut previous = Colour::new(0.0, 0.0, 0.0);
+            for i in 1..=400 {
+                // Grey values from 2^-12 up to 2^8
+                let value = 2f64.powf(i as f64 / 20.0 - 12.0);
+                let mapped = tone_mapping.apply(Colour::new(value, value, value));
+                for (c, p) in [
+                    (mapped.x, previous.x),
+                    (mapped.y, previous.y),
+                    (mapped.z, previous.z),
+                ] {
+                    assert!(
+                        (0.0..=1.0).contains(&c),
+                        "{:?} maps {} to {}",
+                        operator,
+                        value,
+                        c
+                    );
+                    assert!(c >= p, "{:?} decreases at {}", operator, value);
+                }
+                previous = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_to_one() {
+        let operator = ToneOperator::ExtendedReinhard { white: 4.0 };
+        assert!((operator.apply(Colour::new(4.0, 4.0, 4.0)).x - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn parse() {
+        let parse = |s: &str| s.parse::<ToneOperator>();
+        assert_eq!(parse("clamp").unwrap(), ToneOperator::Clamp);
+        assert_eq!(parse("reinhard").unwrap(), ToneOperator::Reinhard);
+        assert_eq!(
+            parse("reinhard-extended").unwrap(),
+            ToneOperator::ExtendedReinhard { white: 4.0 }
+        );
+        assert_eq!(
+            parse("reinhard-extended:2.5").unwrap(),
+            ToneOperator::ExtendedReinhard { white: 2.5 }
+        );
+        assert_eq!(parse("aces").unwrap(), ToneOperator::Aces);
+        assert_eq!(parse("agx").unwrap(), ToneOperator::Agx);
+
+        for s in [
+            "",
+            "filmic",
+            "Clamp",
+            "reinhard:2",
+            "aces:1",
+            "reinhard-extended:",
+            "reinhard-extended:bright",
+            "reinhard-extended:0",
+            "reinhard-extended:-1",
+            "reinhard-extended:inf",
+            "reinhard-extended:NaN",
+        ] {
+            assert!(parse(s).is_err(), "'{}' should be rejected", s);
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use crate::aabb::Aabb;
+use crate::ray::RayR3;
+use crate::vec3::{Vec3, VecR3};
+use std::ops::Mul;
+
+/// A 4x4 matrix acting on homogeneous coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    /// Matrix elements indexed by row then column
+    pub rows: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(rows: [[f64; 4]; 4]) -> Self {
+        Self { rows }
+    }
+
+    pub fn identity() -> Self {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Self { rows }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = self.rows[j][i];
+            }
+        }
+        Self { rows }
+    }
+
+    /// The inverse matrix, found by Gauss-Jordan elimination with partial pivoting.
+    ///
+    /// Returns None if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.rows;
+        let mut inverse = Self::identity().rows;
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+                .unwrap();
+            if a[pivot][column] == 0.0 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = a[column][column].recip();
+            for j in 0..4 {
+                a[column][j] *= scale;
+                inverse[column][j] *= scale;
+            }
+            for i in 0..4 {
+                if i == column {
+                    continue;
+                }
+                let factor = a[i][column];
+                for j in 0..4 {
+                    a[i][j] -= factor * a[column][j];
+                    inverse[i][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+        Some(Self { rows: inverse })
+    }
+
+    /// Determinant of the upper left 3x3 block, which acts on vectors
+    pub fn linear_determinant(&self) -> f64 {
+        let m = &self.rows;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Multiply a point with homogeneous coordinate 1.
+    pub fn transform_point(&self, p: VecR3) -> VecR3 {
+        let m = &self.rows;
+        let row = |r: &[f64; 4]| r[0] * p.x + r[1] * p.y + r[2] * p.z + r[3];
+        let point = Vec3::new(row(&m[0]), row(&m[1]), row(&m[2]));
+        let w = row(&m[3]);
+        if w == 1.0 {
+            point
+        } else {
+            point / w
+        }
+    }
+
+    /// Multiply a vector with homogeneous coordinate 0.
+    pub fn transform_vector(&self, v: VecR3) -> VecR3 {
+        let m = &self.rows;
+        let row = |r: &[f64; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+        Vec3::new(row(&m[0]), row(&m[1]), row(&m[2]))
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Self;
+
+    /// Matrix multiplication
+    fn mul(self, other: Self) -> Self {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| self.rows[i][k] * other.rows[k][j]).sum();
+            }
+        }
+        Self { rows }
+    }
+}
+
+/// An invertible affine transformation of R^3
+///
+/// Stores the matrix together with its inverse, which is needed to transform normals
+/// and to move rays into the space of a transformed object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    /// Create a transform from a matrix.
+    ///
+    /// Returns None if the matrix is not invertible or its bottom row is not `[0, 0, 0, 1]`,
+    /// since projective transforms would not map rays to rays.
+    pub fn new(matrix: Matrix4) -> Option<Self> {
+        if matrix.rows[3] != [0.0, 0.0, 0.0, 1.0] {
+            return None;
+        }
+        let inverse = matrix.inverse()?;
+        Some(Self { matrix, inverse })
+    }
+
+    pub fn identity() -> Self {
+        Self {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    /// Move points by an offset.
+    pub fn translation(offset: VecR3) -> Self {
+        let matrix = |d: VecR3| {
+            Matrix4::new([
+                [1.0, 0.0, 0.0, d.x],
+                [0.0, 1.0, 0.0, d.y],
+                [0.0, 0.0, 1.0, d.z],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+        };
+        Self {
+            matrix: matrix(offset),
+            inverse: matrix(-offset),
+        }
+    }
+
+    /// Scale each axis by a factor; the factors must be non-zero.
+    pub fn scaling(factors: VecR3) -> Self {
+        let matrix = |s: VecR3| {
+            Matrix4::new([
+                [s.x, 0.0, 0.0, 0.0],
+                [0.0, s.y, 0.0, 0.0],
+                [0.0, 0.0, s.z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+        };
+        Self {
+            matrix: matrix(factors),
+            inverse: matrix(factors.map(f64::recip)),
+        }
+    }
+
+    /// Rotate about an axis through the origin.
+    ///
+    /// # Arguments
+    /// * `axis` - Direction of the axis of rotation; need not be unit length.
+    /// * `degrees` - Counter-clockwise angle of rotation when looking down the axis.
+    pub fn rotation(axis: VecR3, degrees: f64) -> Self {
+        let Vec3 { x, y, z } = axis.as_unit();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let c = 1.0 - cos;
+        let matrix = Matrix4::new([
+            [
+                x * x * c + cos,
+                x * y * c - z * sin,
+                x * z * c + y * sin,
+                0.0,
+            ],
+            [
+                y * x * c + z * sin,
+                y * y * c + cos,
+                y * z * c - x * sin,
+                0.0,
+            ],
+            [
+                z * x * c - y * sin,
+                z * y * c + x * sin,
+                z * z * c + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        // Rotation matrices are orthogonal
+        Self {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    /// The transform that applies this transform followed by `next`.
+    pub fn then(&self, next: &Self) -> Self {
+        Self {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn point(&self, p: VecR3) -> VecR3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: VecR3) -> VecR3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Transform a surface normal; the result is not unit length.
+    ///
+    /// Normals are multiplied by the inverse transpose so that they stay perpendicular
+    /// to transformed surfaces.
+    pub fn normal(&self, n: VecR3) -> VecR3 {
+        let m = &self.inverse.rows;
+        let column = |j: usize| m[0][j] * n.x + m[1][j] * n.y + m[2][j] * n.z;
+        Vec3::new(column(0), column(1), column(2))
+    }
+
+    /// Transform a ray.
+    ///
+    /// The direction is not normalized so ray positions `t` are the same in both spaces.
+    pub fn ray(&self, ray: &RayR3) -> RayR3 {
+        RayR3::new(self.point(ray.origin), self.vector(ray.direction))
+    }
+
+    /// A bounding box containing the transformed box.
+    pub fn bounding_box(&self, aabb: &Aabb) -> Aabb {
+        if aabb.is_empty() {
+            return *aabb;
+        }
+        (0..8).fold(Aabb::empty(), |acc, corner| {
+            let pick = |bit: usize, axis: usize| {
+                if corner & (1 << bit) == 0 {
+                    aabb.min[axis]
+                } else {
+                    aabb.max[axis]
+                }
+            };
+            acc.grow(self.point(Vec3::new(pick(0, 0), pick(1, 1), pick(2, 2))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::SampleRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_vector(rng: &mut SampleRng) -> VecR3 {
+        Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        )
+    }
+
+    fn assert_close(a: VecR3, b: VecR3) {
+        assert!((a - b).norm() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn inverse_times_matrix_is_identity() {
+        let mut rng = SampleRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let mut rows = [[0.0; 4]; 4];
+            for row in &mut rows[..3] {
+                for x in row.iter_mut() {
+                    *x = rng.gen_range(-2.0..2.0);
+                }
+            }
+            rows[3][3] = 1.0;
+            let matrix = Matrix4::new(rows);
+            let transform = Transform::new(matrix).unwrap();
+            for product in [transform.inverse * matrix, matrix * transform.inverse] {
+                for i in 0..4 {
+                    for j in 0..4 {
+                        let expected = if i == j { 1.0 } else { 0.0 };
+                        assert!((product.rows[i][j] - expected).abs() < 1e-9);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn new_rejects_singular_and_projective_matrices() {
+        let mut singular = Matrix4::identity();
+        singular.rows[2] = [1.0, 2.0, 0.0, 0.0];
+        singular.rows[1] = [2.0, 4.0, 0.0, 3.0];
+        assert!(Transform::new(singular).is_none());
+
+        let mut projective = Matrix4::identity();
+        projective.rows[3] = [0.0, 0.0, 1.0, 1.0];
+        assert!(projective.inverse().is_some());
+        assert!(Transform::new(projective).is_none());
+        let mut scaled = Matrix4::identity();
+        scaled.rows[3][3] = 2.0;
+        assert!(Transform::new(scaled).is_none());
+    }
+
+    #[test]
+    fn rotation() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        let quarter = Transform::rotation(Vec3::new(0.0, 0.0, 2.0), 90.0);
+        assert_close(quarter.vector(x), y);
+        assert_close(quarter.vector(y), -x);
+        assert_close(quarter.vector(z), z);
+        // A third of a turn about the diagonal cycles the axes
+        let third = Transform::rotation(Vec3::new(1.0, 1.0, 1.0), 120.0);
+        assert_close(third.point(x), y);
+        assert_close(third.point(y), z);
+        assert_close(third.point(z), x);
+
+        let mut rng = SampleRng::seed_from_u64(2);
+        let rotation = Transform::rotation(random_vector(&mut rng), 37.0);
+        for _ in 0..100 {
+            let v = random_vector(&mut rng);
+            assert!((rotation.vector(v).norm() - v.norm()).abs() < 1e-12);
+            assert_close(rotation.inverse().vector(rotation.vector(v)), v);
+        }
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let transform = Transform::scaling(Vec3::new(3.0, 0.5, -2.0))
+            .then(&Transform::rotation(Vec3::new(1.0, 2.0, 3.0), 50.0))
+            .then(&Transform::translation(Vec3::new(4.0, 5.0, 6.0)));
+        let mut rng = SampleRng::seed_from_u64(3);
+        for _ in 0..100 {
+            let normal = random_vector(&mut rng).as_unit();
+            // A tangent perpendicular to the normal
+            let tangent = normal.cross(random_vector(&mut rng));
+            let world_normal = transform.normal(normal).as_unit();
+            let world_tangent = transform.vector(tangent).as_unit();
+            assert!(world_normal.dot(world_tangent).abs() < 1e-9);
+        }
+        // The normal of a plane squashed along one axis tilts towards that axis
+        let squash = Transform::scaling(Vec3::new(1.0, 0.5, 1.0));
+        let normal = squash.normal(Vec3::new(1.0, 1.0, 0.0));
+        assert_close(normal.as_unit(), Vec3::new(1.0, 2.0, 0.0).as_unit());
+    }
+
+    #[test]
+    fn then_applies_in_order() {
+        let transform = Transform::translation(Vec3::new(1.0, 0.0, 0.0))
+            .then(&Transform::scaling(Vec3::new(2.0, 3.0, 4.0)));
+        let p = Vec3::new(1.0, 1.0, 1.0);
+        assert_close(transform.point(p), Vec3::new(4.0, 3.0, 4.0));
+        assert_close(transform.inverse().point(Vec3::new(4.0, 3.0, 4.0)), p);
+    }
+}
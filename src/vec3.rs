@@ -2,10 +2,12 @@ use num_traits::real::Real;
 use rand::distributions::{Distribution, Standard};
 use rand::Rng;
 use std::fmt;
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
+};
 
 /// 3-dimensional vector
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Vec3<T> {
     pub x: T,
     pub y: T,
@@ -20,6 +22,11 @@ impl<T> Vec3<T> {
         Self { x, y, z }
     }
 
+    /// Apply a function to each element
+    pub fn map<U, F: FnMut(T) -> U>(self, mut f: F) -> Vec3<U> {
+        Vec3::new(f(self.x), f(self.y), f(self.z))
+    }
+
     /// Inner product
     pub fn dot<U>(self, other: Vec3<U>) -> <T as Mul<U>>::Output
     where
@@ -88,6 +95,24 @@ where
 }
 
 impl Vec3<f64> {
+    /// Elementwise minimum
+    pub fn min(self, other: Self) -> Self {
+        Vec3::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    /// Elementwise maximum
+    pub fn max(self, other: Self) -> Self {
+        Vec3::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+
     /// Check if all dimensions are close to zero.
     pub fn near_zero(&self) -> bool {
         let s = 1e-8;
@@ -105,8 +130,8 @@ impl Vec3<f64> {
     ///
     /// * `normal` - Normal vector to the refraction plane
     /// * `refraction_ratio` - Ratio eta / eta' where
-    ///                 eta is the index of refraction of the source medium and
-    ///                 eta' is the index of refraction of the destination medium.
+    ///   eta is the index of refraction of the source medium and
+    ///   eta' is the index of refraction of the destination medium.
     ///
     /// # Returns
     /// * `Some(refraction)` - if refraction can occur
@@ -125,6 +150,12 @@ impl Vec3<f64> {
     }
 }
 
+impl<T> From<[T; 3]> for Vec3<T> {
+    fn from([x, y, z]: [T; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
 impl<T: fmt::Display> fmt::Display for Vec3<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "({}, {}, {})", self.x, self.y, self.y)
@@ -143,25 +174,30 @@ impl<T: Clone> Clone for Vec3<T> {
     }
 }
 
-// impl<T, I> Index<I> for Vec3<T>
-// where
-//     [T]: Index<I>,
-// {
-//     type Output = <[T] as Index<I>>::Output;
-
-//     fn index(&self, index: I) -> &Self::Output {
-//         self.0.index(index)
-//     }
-// }
-
-// impl<T, I> IndexMut<I> for Vec3<T>
-// where
-//     [T]: IndexMut<I>,
-// {
-//     fn index_mut(&mut self, index: I) -> &mut Self::Output {
-//         self.0.index_mut(index)
-//     }
-// }
+impl<T> Index<usize> for Vec3<T> {
+    type Output = T;
+
+    /// Access a coordinate by axis index: 0 => x, 1 => y, 2 => z
+    fn index(&self, axis: usize) -> &T {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis index out of range: {}", axis),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Vec3<T> {
+    fn index_mut(&mut self, axis: usize) -> &mut T {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vec3 axis index out of range: {}", axis),
+        }
+    }
+}
 
 impl<T: Neg> Neg for Vec3<T> {
     type Output = Vec3<<T as Neg>::Output>;